@@ -0,0 +1,31 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Wraps a future returned by an async WIT import so that it can be used where `Send` is required.
+///
+/// Futures returned by async imports hold pointers into linear memory and so are not `Send`, but
+/// the OpenTelemetry SDK's exporter traits require `Send` futures. WebAssembly components built
+/// without the `atomics` target feature are single threaded, so these futures are never actually
+/// moved to another thread. On any other target, this is only `Send` if the future is.
+pub(crate) struct AssertSend<F>(Pin<Box<F>>);
+
+impl<F> AssertSend<F> {
+    pub(crate) fn new(future: F) -> Self {
+        Self(Box::pin(future))
+    }
+}
+
+// SAFETY: WebAssembly components without threads run on a single thread, see above.
+#[cfg(all(target_arch = "wasm32", not(target_feature = "atomics")))]
+unsafe impl<F> Send for AssertSend<F> {}
+
+impl<F: Future> Future for AssertSend<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.0.as_mut().poll(cx)
+    }
+}
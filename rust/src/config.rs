@@ -0,0 +1,282 @@
+use crate::{
+    wit::config::wasi::otel::config::{self, preferred_config},
+    WasiLogProcessorBuilder, WasiMetricExporterBuilder,
+};
+use opentelemetry::{logs::Severity, otel_warn};
+use opentelemetry_sdk::{
+    metrics::Temporality,
+    trace::{Sampler, SpanLimits, TracerProviderBuilder},
+};
+
+/// Telemetry configuration preferred by the WASI host.
+///
+/// This lets operators tune sampling, span limits, log severity and metric temporality without
+/// rebuilding components. Settings the host leaves unset keep the guest's defaults.
+///
+/// # Example
+/// ```ignore
+/// let config = HostConfig::from_host();
+/// let tracer_provider = config
+///     .configure_tracer_provider(SdkTracerProvider::builder())
+///     .with_span_processor(WasiSpanProcessor::new())
+///     .build();
+/// let log_processor = config
+///     .configure_log_processor(WasiLogProcessor::builder())
+///     .build();
+/// let exporter = config
+///     .configure_metric_exporter(WasiMetricExporter::builder())
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HostConfig {
+    sampler: Option<Sampler>,
+    span_limits: Option<SpanLimits>,
+    min_log_severity: Option<Severity>,
+    metric_temporality: Option<Temporality>,
+}
+
+impl HostConfig {
+    /// Fetch the preferred configuration from the host.
+    pub fn from_host() -> Self {
+        preferred_config().into()
+    }
+
+    /// The sampler preferred by the host.
+    ///
+    /// Sampling ratios outside the range 0.0 to 1.0 are clamped to it, with a warning.
+    pub fn sampler(&self) -> Option<&Sampler> {
+        self.sampler.as_ref()
+    }
+
+    /// The span limits preferred by the host.
+    pub fn span_limits(&self) -> Option<SpanLimits> {
+        self.span_limits
+    }
+
+    /// The minimum severity of log records the host wants to receive.
+    pub fn min_log_severity(&self) -> Option<Severity> {
+        self.min_log_severity
+    }
+
+    /// The metric temporality preferred by the host.
+    pub fn metric_temporality(&self) -> Option<Temporality> {
+        self.metric_temporality
+    }
+
+    /// Apply the host's sampler and span limits to a tracer provider.
+    pub fn configure_tracer_provider(
+        &self,
+        mut builder: TracerProviderBuilder,
+    ) -> TracerProviderBuilder {
+        if let Some(sampler) = &self.sampler {
+            builder = builder.with_sampler(sampler.clone());
+        }
+        if let Some(span_limits) = self.span_limits {
+            builder = builder.with_span_limits(span_limits);
+        }
+        builder
+    }
+
+    /// Apply the host's minimum log severity to a log processor.
+    ///
+    /// The `SdkLoggerProvider` has no severity filter of its own, so filtering happens in the
+    /// `WasiLogProcessor` before records cross the component boundary.
+    pub fn configure_log_processor(
+        &self,
+        mut builder: WasiLogProcessorBuilder,
+    ) -> WasiLogProcessorBuilder {
+        if let Some(severity) = self.min_log_severity {
+            builder = builder.with_min_severity(severity);
+        }
+        builder
+    }
+
+    /// Apply the host's metric temporality to a metric exporter.
+    pub fn configure_metric_exporter(
+        &self,
+        mut builder: WasiMetricExporterBuilder,
+    ) -> WasiMetricExporterBuilder {
+        if let Some(temporality) = self.metric_temporality {
+            builder = builder.with_temporality(temporality);
+        }
+        builder
+    }
+}
+
+impl From<config::HostConfig> for HostConfig {
+    fn from(value: config::HostConfig) -> Self {
+        Self {
+            sampler: value.sampler.map(Into::into),
+            span_limits: value.span_limits.map(Into::into),
+            min_log_severity: value.min_log_severity.and_then(severity_from_number),
+            metric_temporality: value.metric_temporality.map(Into::into),
+        }
+    }
+}
+
+impl From<config::Sampler> for Sampler {
+    fn from(value: config::Sampler) -> Self {
+        match value {
+            config::Sampler::AlwaysOn => Sampler::AlwaysOn,
+            config::Sampler::AlwaysOff => Sampler::AlwaysOff,
+            config::Sampler::TraceIdRatioBased(ratio) => {
+                Sampler::TraceIdRatioBased(clamp_ratio(ratio))
+            }
+            config::Sampler::ParentBasedAlwaysOn => {
+                Sampler::ParentBased(Box::new(Sampler::AlwaysOn))
+            }
+            config::Sampler::ParentBasedAlwaysOff => {
+                Sampler::ParentBased(Box::new(Sampler::AlwaysOff))
+            }
+            config::Sampler::ParentBasedTraceIdRatioBased(ratio) => {
+                Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(clamp_ratio(ratio))))
+            }
+        }
+    }
+}
+
+/// Clamps a sampling ratio from the host to the range 0.0 to 1.0, treating `NaN` as 0.0.
+fn clamp_ratio(ratio: f64) -> f64 {
+    if (0.0..=1.0).contains(&ratio) {
+        return ratio;
+    }
+    let clamped = if ratio.is_nan() {
+        0.0
+    } else {
+        ratio.clamp(0.0, 1.0)
+    };
+    otel_warn!(name: "host_config_invalid_sampler_ratio", msg = format!("Clamping sampler ratio {} from host to {}", ratio, clamped));
+    clamped
+}
+
+impl From<config::SpanLimits> for SpanLimits {
+    fn from(value: config::SpanLimits) -> Self {
+        Self {
+            max_events_per_span: value.max_events_per_span,
+            max_attributes_per_span: value.max_attributes_per_span,
+            max_links_per_span: value.max_links_per_span,
+            max_attributes_per_event: value.max_attributes_per_event,
+            max_attributes_per_link: value.max_attributes_per_link,
+        }
+    }
+}
+
+impl From<config::Temporality> for Temporality {
+    fn from(value: config::Temporality) -> Self {
+        match value {
+            config::Temporality::Cumulative => Temporality::Cumulative,
+            config::Temporality::Delta => Temporality::Delta,
+            config::Temporality::LowMemory => Temporality::LowMemory,
+        }
+    }
+}
+
+/// Maps a severity number from 1-24 to a `Severity`.
+fn severity_from_number(value: u8) -> Option<Severity> {
+    Some(match value {
+        1 => Severity::Trace,
+        2 => Severity::Trace2,
+        3 => Severity::Trace3,
+        4 => Severity::Trace4,
+        5 => Severity::Debug,
+        6 => Severity::Debug2,
+        7 => Severity::Debug3,
+        8 => Severity::Debug4,
+        9 => Severity::Info,
+        10 => Severity::Info2,
+        11 => Severity::Info3,
+        12 => Severity::Info4,
+        13 => Severity::Warn,
+        14 => Severity::Warn2,
+        15 => Severity::Warn3,
+        16 => Severity::Warn4,
+        17 => Severity::Error,
+        18 => Severity::Error2,
+        19 => Severity::Error3,
+        20 => Severity::Error4,
+        21 => Severity::Fatal,
+        22 => Severity::Fatal2,
+        23 => Severity::Fatal3,
+        24 => Severity::Fatal4,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samplers don't implement `PartialEq`, so they are compared by their debug output.
+    fn assert_maps_to(sampler: config::Sampler, expected: Sampler) {
+        assert_eq!(
+            format!("{:?}", Sampler::from(sampler)),
+            format!("{:?}", expected)
+        );
+    }
+
+    #[test]
+    fn map_samplers() {
+        assert_maps_to(config::Sampler::AlwaysOn, Sampler::AlwaysOn);
+        assert_maps_to(config::Sampler::AlwaysOff, Sampler::AlwaysOff);
+        assert_maps_to(
+            config::Sampler::TraceIdRatioBased(0.25),
+            Sampler::TraceIdRatioBased(0.25),
+        );
+        assert_maps_to(
+            config::Sampler::ParentBasedAlwaysOn,
+            Sampler::ParentBased(Box::new(Sampler::AlwaysOn)),
+        );
+        assert_maps_to(
+            config::Sampler::ParentBasedAlwaysOff,
+            Sampler::ParentBased(Box::new(Sampler::AlwaysOff)),
+        );
+        assert_maps_to(
+            config::Sampler::ParentBasedTraceIdRatioBased(0.5),
+            Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(0.5))),
+        );
+    }
+
+    #[test]
+    fn clamp_out_of_range_sampler_ratios() {
+        assert_maps_to(
+            config::Sampler::TraceIdRatioBased(1.5),
+            Sampler::TraceIdRatioBased(1.0),
+        );
+        assert_maps_to(
+            config::Sampler::TraceIdRatioBased(-0.5),
+            Sampler::TraceIdRatioBased(0.0),
+        );
+        assert_maps_to(
+            config::Sampler::ParentBasedTraceIdRatioBased(f64::NAN),
+            Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(0.0))),
+        );
+    }
+    #[test]
+    fn map_severity_numbers() {
+        assert_eq!(severity_from_number(0), None);
+        assert_eq!(severity_from_number(1), Some(Severity::Trace));
+        assert_eq!(severity_from_number(9), Some(Severity::Info));
+        assert_eq!(severity_from_number(17), Some(Severity::Error));
+        assert_eq!(severity_from_number(24), Some(Severity::Fatal4));
+        assert_eq!(severity_from_number(25), None);
+        for number in 1..=24 {
+            assert_eq!(severity_from_number(number).map(|s| s as u8), Some(number));
+        }
+    }
+
+    #[test]
+    fn map_temporalities() {
+        assert_eq!(
+            Temporality::from(config::Temporality::Cumulative),
+            Temporality::Cumulative
+        );
+        assert_eq!(
+            Temporality::from(config::Temporality::Delta),
+            Temporality::Delta
+        );
+        assert_eq!(
+            Temporality::from(config::Temporality::LowMemory),
+            Temporality::LowMemory
+        );
+    }
+}
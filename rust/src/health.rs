@@ -0,0 +1,217 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+
+/// A snapshot of how exports to the WASI host have fared.
+///
+/// Returned by `health()` on the span and log exporters and processors, and on their
+/// [`ExportHealthHandle`], so applications can detect when the host is dropping telemetry.
+///
+/// Only the `wasi:otel/batch-export`, `wasi:otel/async-export` and `wasi:otel/otlp` interfaces
+/// report whether the host accepted an export. Spans and log records sent one at a time through
+/// `wasi:otel/tracing.on-end` and `wasi:otel/logs.on-emit` are not counted, and `force_flush`
+/// never fails for them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExportHealth {
+    successful_exports: u64,
+    failed_exports: u64,
+    last_error: Option<String>,
+}
+
+impl ExportHealth {
+    /// The number of batches the host accepted.
+    pub fn successful_exports(&self) -> u64 {
+        self.successful_exports
+    }
+
+    /// The number of batches the host rejected.
+    pub fn failed_exports(&self) -> u64 {
+        self.failed_exports
+    }
+
+    /// The error returned by the host for the most recent failed export, if any.
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+}
+
+/// The telemetry signal carried by an export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Signal {
+    Spans,
+    Logs,
+    Metrics,
+}
+
+impl Signal {
+    pub(crate) const ALL: [Signal; 3] = [Signal::Spans, Signal::Logs, Signal::Metrics];
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Signal::Spans => "spans",
+            Signal::Logs => "logs",
+            Signal::Metrics => "metrics",
+        }
+    }
+}
+
+/// The number of successful and failed exports of each signal across the component instance.
+static INSTANCE_EXPORTS: [[AtomicU64; 2]; 3] = [const { [const { AtomicU64::new(0) }; 2] }; 3];
+
+/// Counts an export towards the totals of the component instance.
+pub(crate) fn record_instance_export(signal: Signal, succeeded: bool) {
+    INSTANCE_EXPORTS[signal as usize][usize::from(!succeeded)].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Returns the number of successful and failed exports of `signal` across the component instance.
+pub(crate) fn instance_exports(signal: Signal) -> (u64, u64) {
+    let [succeeded, failed] = &INSTANCE_EXPORTS[signal as usize];
+    (
+        succeeded.load(Ordering::Relaxed),
+        failed.load(Ordering::Relaxed),
+    )
+}
+
+/// The number of metric data points that could not be delivered to the host.
+static DROPPED_METRIC_POINTS: AtomicU64 = AtomicU64::new(0);
+
+/// Counts metric data points that were lost after the host rejected an export.
+pub(crate) fn record_dropped_metric_points(count: usize) {
+    if count > 0 {
+        DROPPED_METRIC_POINTS.fetch_add(count as u64, Ordering::Relaxed);
+    }
+}
+
+/// Returns the number of metric data points lost across the component instance.
+pub(crate) fn dropped_metric_points() -> u64 {
+    DROPPED_METRIC_POINTS.load(Ordering::Relaxed)
+}
+
+/// The number of array elements and map entries dropped by `PayloadLimits`.
+static DROPPED_VALUE_ELEMENTS: AtomicU64 = AtomicU64::new(0);
+
+/// Counts array elements and map entries dropped from values by the array length limit.
+pub(crate) fn record_dropped_value_elements(count: u64) {
+    if count > 0 {
+        DROPPED_VALUE_ELEMENTS.fetch_add(count, Ordering::Relaxed);
+    }
+}
+
+/// Returns the number of array elements and map entries dropped across the component instance.
+pub(crate) fn dropped_value_elements() -> u64 {
+    DROPPED_VALUE_ELEMENTS.load(Ordering::Relaxed)
+}
+
+/// A handle to the export health of an exporter or processor.
+///
+/// Obtained from `health_handle()` on the exporter and processor builders, so health can still be
+/// read once the exporter or processor is owned by a provider.
+///
+/// # Example
+/// ```ignore
+/// let builder = WasiSpanProcessor::builder();
+/// let health = builder.health_handle();
+/// let provider = SdkTracerProvider::builder()
+///     .with_span_processor(builder.build())
+///     .build();
+/// // ...
+/// if health.health().failed_exports() > 0 { /* ... */ }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ExportHealthHandle {
+    state: Arc<Mutex<TrackerState>>,
+}
+
+impl ExportHealthHandle {
+    /// Returns how exports to the host have fared so far.
+    pub fn health(&self) -> ExportHealth {
+        self.state.lock().unwrap().health.clone()
+    }
+}
+
+/// Records the outcome of every export so failures can be reported on the next flush.
+///
+/// Outcomes are only reported by the host interfaces enabled by the `batch-export` and `otlp`
+/// features, so nothing is recorded without them.
+#[derive(Debug)]
+#[cfg_attr(not(any(feature = "batch-export", feature = "otlp")), allow(dead_code))]
+pub(crate) struct ExportTracker {
+    signal: Signal,
+    state: Arc<Mutex<TrackerState>>,
+}
+
+#[derive(Debug, Default)]
+struct TrackerState {
+    health: ExportHealth,
+    failed_since_flush: u64,
+}
+
+#[cfg_attr(not(any(feature = "batch-export", feature = "otlp")), allow(dead_code))]
+impl ExportTracker {
+    /// Creates a tracker that reports its health through `handle`.
+    pub(crate) fn new(signal: Signal, handle: ExportHealthHandle) -> Self {
+        Self {
+            signal,
+            state: handle.state,
+        }
+    }
+
+    pub(crate) fn record_success(&self) {
+        record_instance_export(self.signal, true);
+        self.state.lock().unwrap().health.successful_exports += 1;
+    }
+
+    pub(crate) fn record_failure(&self, error: &str) {
+        record_instance_export(self.signal, false);
+        let mut state = self.state.lock().unwrap();
+        state.health.failed_exports += 1;
+        state.health.last_error = Some(error.to_string());
+        state.failed_since_flush += 1;
+    }
+
+    /// Returns an error describing the exports that failed since the last call, if any.
+    pub(crate) fn take_flush_error(&self) -> Option<String> {
+        let mut state = self.state.lock().unwrap();
+        let failed = std::mem::take(&mut state.failed_since_flush);
+        (failed > 0).then(|| {
+            format!(
+                "{} export(s) failed since the last flush, last error: {}",
+                failed,
+                state.health.last_error.as_deref().unwrap_or_default()
+            )
+        })
+    }
+
+    pub(crate) fn health(&self) -> ExportHealth {
+        self.state.lock().unwrap().health.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flush_error_reports_failures_once() {
+        let handle = ExportHealthHandle::default();
+        let tracker = ExportTracker::new(Signal::Spans, handle.clone());
+        tracker.record_success();
+        assert_eq!(tracker.take_flush_error(), None);
+
+        tracker.record_failure("first");
+        tracker.record_failure("second");
+        tracker.record_success();
+        assert_eq!(
+            tracker.take_flush_error().as_deref(),
+            Some("2 export(s) failed since the last flush, last error: second")
+        );
+        assert_eq!(tracker.take_flush_error(), None);
+
+        let health = tracker.health();
+        assert_eq!(handle.health(), health);
+        assert_eq!(health.successful_exports(), 2);
+        assert_eq!(health.failed_exports(), 2);
+        assert_eq!(health.last_error(), Some("second"));
+    }
+}
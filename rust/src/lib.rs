@@ -1,10 +1,38 @@
+#[cfg(all(
+    feature = "async-export",
+    any(test, all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+mod async_support;
+#[cfg(feature = "config")]
+mod config;
+mod health;
+mod limits;
 mod logs;
 mod metrics;
+#[cfg(feature = "otlp")]
+mod otlp;
+mod panic_hook;
+#[cfg(test)]
+mod recording_host;
+#[cfg(feature = "redaction")]
+mod redaction;
+#[cfg(not(feature = "redaction"))]
+#[path = "no_redaction.rs"]
+mod redaction;
 mod tracing;
 mod types;
 
+#[cfg(feature = "config")]
+pub use config::*;
+pub use health::*;
+pub use limits::*;
 pub use logs::*;
 pub use metrics::*;
+pub use panic_hook::*;
+#[cfg(not(feature = "redaction"))]
+use redaction::Redactor;
+#[cfg(feature = "redaction")]
+pub use redaction::*;
 pub use tracing::*;
 
 #[doc(hidden)]
@@ -19,4 +47,57 @@ mod wit {
         path: "../wit",
         generate_all,
     });
+
+    /// Generates the bindings of a world that extends `imports`, reusing the `imports` bindings so
+    /// that only the interfaces the world adds are generated.
+    ///
+    /// Each of these worlds is behind a feature, so that components only import the optional
+    /// interfaces they use and keep working with hosts that only implement `imports`.
+    #[allow(unused_macros)]
+    macro_rules! generate_extension {
+        ($world:tt $(, $interface:tt => $module:path)* $(,)?) => {
+            wit_bindgen::generate!({
+                world: $world,
+                path: "../wit",
+                with: {
+                    "wasi:io/poll@0.2.0": crate::wit::wasi::io::poll,
+                    "wasi:clocks/monotonic-clock@0.2.0": crate::wit::wasi::clocks::monotonic_clock,
+                    "wasi:clocks/wall-clock@0.2.0": crate::wit::wasi::clocks::wall_clock,
+                    "wasi:otel/types@0.2.0-rc.2": crate::wit::wasi::otel::types,
+                    "wasi:otel/tracing@0.2.0-rc.2": crate::wit::wasi::otel::tracing,
+                    "wasi:otel/metrics@0.2.0-rc.2": crate::wit::wasi::otel::metrics,
+                    "wasi:otel/logs@0.2.0-rc.2": crate::wit::wasi::otel::logs,
+                    $($interface: $module,)*
+                },
+            });
+        };
+    }
+
+    #[cfg(feature = "span-start")]
+    pub mod span_start {
+        generate_extension!("wasi:otel/imports-with-span-start@0.2.0-rc.2");
+    }
+
+    #[cfg(feature = "batch-export")]
+    pub mod batch_export {
+        generate_extension!("wasi:otel/imports-with-batch-export@0.2.0-rc.2");
+    }
+
+    #[cfg(feature = "async-export")]
+    pub mod async_export {
+        generate_extension!(
+            "wasi:otel/imports-with-async-export@0.2.0-rc.2",
+            "wasi:otel/batch-export@0.2.0-rc.2" => crate::wit::batch_export::wasi::otel::batch_export,
+        );
+    }
+
+    #[cfg(feature = "config")]
+    pub mod config {
+        generate_extension!("wasi:otel/imports-with-config@0.2.0-rc.2");
+    }
+
+    #[cfg(feature = "otlp")]
+    pub mod otlp {
+        generate_extension!("wasi:otel/imports-with-otlp@0.2.0-rc.2");
+    }
 }
@@ -0,0 +1,351 @@
+use crate::health::record_dropped_value_elements;
+use opentelemetry::{logs::AnyValue, Array, KeyValue, StringValue, Value};
+use std::{borrow::Cow, iter::Take};
+
+/// Caps the amount of telemetry data copied across the component boundary.
+///
+/// `PayloadLimits` can be attached to the `WasiSpanProcessor`, `WasiLogProcessor` and
+/// `WasiMetricExporter` builders. Anything over a limit is truncated while converting to WIT
+/// records, and the number of dropped attributes, events and links is added to the counts
+/// reported to the host. The exception is log records sent through `wasi:otel/logs.on-emit`, the
+/// default: `log-record` has no count of dropped attributes, so the host is not told how many were
+/// dropped. Log records sent through `wasi:otel/batch-export` or `wasi:otel/otlp` report it.
+///
+/// The attributes of metric data points are never limited, as dropping or truncating them could
+/// merge distinct series into one. Only the filtered attributes of exemplars are.
+///
+/// String values longer than the value length limit are truncated to the limit in bytes, on a
+/// character boundary. This also applies to each element of string arrays, to strings nested in
+/// log bodies and to byte values.
+///
+/// Arrays, and the lists and maps nested in log bodies and attributes, are cut down to the array
+/// length limit at every level of nesting. Which entries of a map are kept is unspecified. The
+/// number of elements and entries dropped this way is reported by the
+/// `wasi.component.telemetry.dropped_value_elements` metric of `WasiRuntimeMetrics`.
+///
+/// All limits are unset by default.
+///
+/// # Example
+/// ```ignore
+/// let limits = PayloadLimits::builder()
+///     .with_max_attributes(64)
+///     .with_max_attribute_value_length(1024)
+///     .build();
+/// let processor = WasiLogProcessor::builder()
+///     .with_limits(limits)
+///     .build();
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PayloadLimits {
+    max_attributes: Option<usize>,
+    max_attribute_value_length: Option<usize>,
+    max_array_length: Option<usize>,
+    max_events: Option<usize>,
+    max_links: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+pub struct PayloadLimitsBuilder {
+    limits: PayloadLimits,
+}
+
+impl PayloadLimitsBuilder {
+    /// The maximum number of attributes on a span, event, link, log record or exemplar.
+    pub fn with_max_attributes(mut self, max: usize) -> Self {
+        self.limits.max_attributes = Some(max);
+        self
+    }
+
+    /// The maximum length in bytes of string and byte values, including log bodies.
+    pub fn with_max_attribute_value_length(mut self, max: usize) -> Self {
+        self.limits.max_attribute_value_length = Some(max);
+        self
+    }
+
+    /// The maximum number of elements in an array value, and of elements or entries in each list
+    /// or map of a log body or attribute.
+    pub fn with_max_array_length(mut self, max: usize) -> Self {
+        self.limits.max_array_length = Some(max);
+        self
+    }
+
+    /// The maximum number of events on a span.
+    pub fn with_max_events(mut self, max: usize) -> Self {
+        self.limits.max_events = Some(max);
+        self
+    }
+
+    /// The maximum number of links on a span.
+    pub fn with_max_links(mut self, max: usize) -> Self {
+        self.limits.max_links = Some(max);
+        self
+    }
+
+    /// Build the limits.
+    pub fn build(self) -> PayloadLimits {
+        self.limits
+    }
+}
+
+impl PayloadLimits {
+    /// Create a new builder for configuring PayloadLimits.
+    pub fn builder() -> PayloadLimitsBuilder {
+        PayloadLimitsBuilder::default()
+    }
+
+    /// Truncates the attributes, events and links of a span, updating its dropped counts.
+    pub(crate) fn limit_span(&self, span: &mut opentelemetry_sdk::trace::SpanData) {
+        span.dropped_attributes_count += self.limit_key_values(&mut span.attributes);
+        span.events.dropped_count += truncate(&mut span.events.events, self.max_events);
+        for event in span.events.events.iter_mut() {
+            event.dropped_attributes_count += self.limit_key_values(&mut event.attributes);
+        }
+        span.links.dropped_count += truncate(&mut span.links.links, self.max_links);
+        for link in span.links.links.iter_mut() {
+            link.dropped_attributes_count += self.limit_key_values(&mut link.attributes);
+        }
+    }
+
+    /// Truncates a list of attributes and their values, returning the number of attributes dropped.
+    pub(crate) fn limit_key_values(&self, attributes: &mut Vec<KeyValue>) -> u32 {
+        let dropped = truncate(attributes, self.max_attributes);
+        if self.limits_values() {
+            let mut dropped_elements = 0;
+            for kv in attributes.iter_mut() {
+                kv.value = self.limit_value(&kv.value, &mut dropped_elements);
+            }
+            record_dropped_value_elements(dropped_elements);
+        }
+        dropped
+    }
+
+    /// Returns whether an attribute at `index` fits within the attribute count limit.
+    pub(crate) fn is_attribute_within_limit(&self, index: usize) -> bool {
+        self.max_attributes.is_none_or(|max| index < max)
+    }
+
+    /// Truncates a log body or attribute value.
+    pub(crate) fn limit_any_value<'a>(&self, value: &'a AnyValue) -> Cow<'a, AnyValue> {
+        if !self.limits_values() {
+            return Cow::Borrowed(value);
+        }
+        let mut dropped_elements = 0;
+        let value = self.truncate_any_value(value, &mut dropped_elements);
+        record_dropped_value_elements(dropped_elements);
+        Cow::Owned(value)
+    }
+
+    fn limits_values(&self) -> bool {
+        self.max_attribute_value_length.is_some() || self.max_array_length.is_some()
+    }
+
+    fn limit_value(&self, value: &Value, dropped_elements: &mut u64) -> Value {
+        match value {
+            Value::String(s) => Value::String(self.truncate_str(s)),
+            Value::Array(Array::Bool(list)) => Value::Array(Array::Bool(
+                self.take(list.iter(), dropped_elements).copied().collect(),
+            )),
+            Value::Array(Array::I64(list)) => Value::Array(Array::I64(
+                self.take(list.iter(), dropped_elements).copied().collect(),
+            )),
+            Value::Array(Array::F64(list)) => Value::Array(Array::F64(
+                self.take(list.iter(), dropped_elements).copied().collect(),
+            )),
+            Value::Array(Array::String(list)) => Value::Array(Array::String(
+                self.take(list.iter(), dropped_elements)
+                    .map(|s| self.truncate_str(s))
+                    .collect(),
+            )),
+            other => other.clone(),
+        }
+    }
+
+    fn truncate_any_value(&self, value: &AnyValue, dropped_elements: &mut u64) -> AnyValue {
+        match value {
+            AnyValue::String(s) => AnyValue::String(self.truncate_str(s)),
+            AnyValue::Bytes(bytes) => match self.max_attribute_value_length {
+                Some(max) if bytes.len() > max => AnyValue::Bytes(Box::new(bytes[..max].to_vec())),
+                _ => value.clone(),
+            },
+            AnyValue::ListAny(list) => AnyValue::ListAny(Box::new(
+                self.take(list.iter(), dropped_elements)
+                    .map(|v| self.truncate_any_value(v, dropped_elements))
+                    .collect(),
+            )),
+            AnyValue::Map(map) => AnyValue::Map(Box::new(
+                self.take(map.iter(), dropped_elements)
+                    .map(|(k, v)| (k.clone(), self.truncate_any_value(v, dropped_elements)))
+                    .collect(),
+            )),
+            other => other.clone(),
+        }
+    }
+
+    /// Returns the elements of `items` within the array length limit, counting the rest.
+    fn take<I: ExactSizeIterator>(&self, items: I, dropped_elements: &mut u64) -> Take<I> {
+        let max = self.max_array_length.unwrap_or(usize::MAX);
+        *dropped_elements += items.len().saturating_sub(max) as u64;
+        items.take(max)
+    }
+
+    fn truncate_str(&self, s: &StringValue) -> StringValue {
+        match self.max_attribute_value_length {
+            Some(max) => truncate_str(s, max),
+            None => s.clone(),
+        }
+    }
+}
+
+/// Truncates `items` to `max` elements, returning the number of elements removed.
+fn truncate<T>(items: &mut Vec<T>, max: Option<usize>) -> u32 {
+    match max {
+        Some(max) if items.len() > max => {
+            let dropped = items.len() - max;
+            items.truncate(max);
+            dropped as u32
+        }
+        _ => 0,
+    }
+}
+
+/// Truncates a string to at most `max` bytes without splitting a character.
+fn truncate_str(s: &StringValue, max: usize) -> StringValue {
+    let s = s.as_str();
+    if s.len() <= max {
+        return s.to_string().into();
+    }
+    let end = (0..=max)
+        .rev()
+        .find(|&i| s.is_char_boundary(i))
+        .unwrap_or(0);
+    s[..end].to_string().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{Event, Link, SpanContext};
+    use opentelemetry_sdk::trace::{SpanData, SpanEvents, SpanLinks};
+    use std::time::SystemTime;
+
+    fn span_data(attributes: usize, events: usize, links: usize) -> SpanData {
+        let kvs = |n: usize| (0..n).map(|i| KeyValue::new(format!("k{i}"), i as i64));
+        let mut span_events = SpanEvents::default();
+        span_events.events = (0..events)
+            .map(|i| {
+                Event::new(
+                    format!("e{i}"),
+                    SystemTime::now(),
+                    kvs(attributes).collect(),
+                    0,
+                )
+            })
+            .collect();
+        let mut span_links = SpanLinks::default();
+        span_links.links = (0..links)
+            .map(|_| Link::new(SpanContext::empty_context(), kvs(attributes).collect(), 0))
+            .collect();
+        SpanData {
+            span_context: SpanContext::empty_context(),
+            parent_span_id: opentelemetry::trace::SpanId::INVALID,
+            parent_span_is_remote: false,
+            span_kind: opentelemetry::trace::SpanKind::Internal,
+            name: Cow::Borrowed("span"),
+            start_time: SystemTime::now(),
+            end_time: SystemTime::now(),
+            attributes: kvs(attributes).collect(),
+            dropped_attributes_count: 1,
+            events: span_events,
+            links: span_links,
+            status: opentelemetry::trace::Status::Unset,
+            instrumentation_scope: Default::default(),
+        }
+    }
+
+    #[test]
+    fn limit_span_updates_dropped_counts() {
+        let limits = PayloadLimits::builder()
+            .with_max_attributes(2)
+            .with_max_events(1)
+            .with_max_links(0)
+            .build();
+        let mut span = span_data(5, 3, 2);
+        limits.limit_span(&mut span);
+
+        assert_eq!(span.attributes.len(), 2);
+        assert_eq!(span.dropped_attributes_count, 4);
+        assert_eq!(span.events.len(), 1);
+        assert_eq!(span.events.dropped_count, 2);
+        assert_eq!(span.events.events[0].attributes.len(), 2);
+        assert_eq!(span.events.events[0].dropped_attributes_count, 3);
+        assert!(span.links.is_empty());
+        assert_eq!(span.links.dropped_count, 2);
+    }
+
+    #[test]
+    fn limit_array_length_and_count_dropped_elements() {
+        let limits = PayloadLimits::builder().with_max_array_length(2).build();
+        let dropped = crate::health::dropped_value_elements();
+        let mut attributes = vec![
+            KeyValue::new("ids", Value::Array(Array::I64(vec![1, 2, 3, 4, 5]))),
+            KeyValue::new("tags", Value::Array(Array::String(vec!["a".into()]))),
+        ];
+        assert_eq!(limits.limit_key_values(&mut attributes), 0);
+        assert_eq!(attributes[0].value, Value::Array(Array::I64(vec![1, 2])));
+        assert_eq!(
+            attributes[1].value,
+            Value::Array(Array::String(vec!["a".into()]))
+        );
+
+        let body = AnyValue::ListAny(Box::new(vec![
+            AnyValue::Map(Box::new(
+                [("a", 1), ("b", 2), ("c", 3)]
+                    .into_iter()
+                    .map(|(k, v)| (k.into(), AnyValue::Int(v)))
+                    .collect(),
+            )),
+            AnyValue::Int(2),
+            AnyValue::Int(3),
+        ]));
+        let AnyValue::ListAny(list) = limits.limit_any_value(&body).into_owned() else {
+            panic!("unexpected body");
+        };
+        assert_eq!(list.len(), 2);
+        let AnyValue::Map(map) = &list[0] else {
+            panic!("unexpected element {:?}", list[0]);
+        };
+        assert_eq!(map.len(), 2);
+        assert_eq!(crate::health::dropped_value_elements() - dropped, 5);
+    }
+
+    #[test]
+    fn limit_value_length_on_char_boundary() {
+        let limits = PayloadLimits::builder()
+            .with_max_attribute_value_length(4)
+            .build();
+        let mut attributes = vec![
+            KeyValue::new("short", "abc"),
+            KeyValue::new("long", "abcdef"),
+            KeyValue::new("multibyte", "aé€"),
+            KeyValue::new(
+                "array",
+                Value::Array(Array::String(vec!["abcdef".into(), "ab".into()])),
+            ),
+            KeyValue::new("int", 123456),
+        ];
+        assert_eq!(limits.limit_key_values(&mut attributes), 0);
+        assert_eq!(
+            attributes,
+            vec![
+                KeyValue::new("short", "abc"),
+                KeyValue::new("long", "abcd"),
+                KeyValue::new("multibyte", "aé"),
+                KeyValue::new(
+                    "array",
+                    Value::Array(Array::String(vec!["abcd".into(), "ab".into()]))
+                ),
+                KeyValue::new("int", 123456),
+            ]
+        );
+    }
+}
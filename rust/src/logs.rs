@@ -1,4 +1,27 @@
+#[cfg(all(
+    feature = "async-export",
+    any(test, all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+mod async_exporter;
 mod conversion;
+mod event;
+mod exporter;
 mod processor;
 
+#[cfg(all(
+    feature = "async-export",
+    any(test, all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+pub use async_exporter::WasiAsyncLogExporter;
+#[cfg(all(
+    feature = "async-export",
+    any(test, all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+pub use async_exporter::WasiAsyncLogExporterBuilder;
+pub use event::WasiEvent;
+pub use event::WasiEventBuilder;
+pub use exporter::WasiLogExporter;
+pub use exporter::WasiLogExporterBuilder;
+pub use processor::TraceContextInjection;
 pub use processor::WasiLogProcessor;
+pub use processor::WasiLogProcessorBuilder;
@@ -0,0 +1,216 @@
+#[cfg(test)]
+use crate::recording_host::export_logs_async as export_logs;
+#[cfg(not(test))]
+use crate::wit::async_export::wasi::otel::async_export::export_logs;
+use crate::{
+    async_support::AssertSend,
+    health::{ExportTracker, Signal},
+    logs::conversion::to_wasi_limited_log_record,
+    ExportHealth, ExportHealthHandle, PayloadLimits, Redactor,
+};
+use opentelemetry::otel_error;
+use opentelemetry_sdk::{
+    error::{OTelSdkError, OTelSdkResult},
+    logs::{LogBatch, LogExporter},
+};
+use std::{
+    future::Future,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// A log exporter that sends batches of log records to a WASI host without blocking.
+///
+/// This uses the asynchronous `wasi:otel/async-export` interface and is meant for wasip3
+/// components running on an async executor, where it can be used with batching log processors.
+/// The resource is provided by the `SdkLoggerProvider` the exporter is registered with. Empty
+/// batches are not sent to the host.
+#[derive(Debug)]
+pub struct WasiAsyncLogExporter {
+    is_shutdown: AtomicBool,
+    resource: Option<opentelemetry_sdk::Resource>,
+    tracker: ExportTracker,
+    redactor: Option<Redactor>,
+    limits: PayloadLimits,
+}
+
+#[derive(Default)]
+pub struct WasiAsyncLogExporterBuilder {
+    redactor: Option<Redactor>,
+    limits: PayloadLimits,
+    health: ExportHealthHandle,
+}
+
+impl WasiAsyncLogExporterBuilder {
+    /// Redact log bodies and attributes before they are sent to the host.
+    #[cfg(feature = "redaction")]
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+
+    /// Truncate log attributes and bodies that exceed `limits`.
+    pub fn with_limits(mut self, limits: PayloadLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Returns a handle for reading the health of the exporter once it is built, even after it is
+    /// moved into a provider or processor.
+    pub fn health_handle(&self) -> ExportHealthHandle {
+        self.health.clone()
+    }
+
+    /// Build the exporter.
+    pub fn build(self) -> WasiAsyncLogExporter {
+        WasiAsyncLogExporter {
+            is_shutdown: AtomicBool::new(false),
+            resource: None,
+            tracker: ExportTracker::new(Signal::Logs, self.health),
+            redactor: self.redactor,
+            limits: self.limits,
+        }
+    }
+}
+
+impl WasiAsyncLogExporter {
+    /// Create a new `WasiAsyncLogExporter`.
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    /// Create a new builder for configuring a WasiAsyncLogExporter.
+    pub fn builder() -> WasiAsyncLogExporterBuilder {
+        WasiAsyncLogExporterBuilder::default()
+    }
+
+    /// Returns how exports to the host have fared so far.
+    pub fn health(&self) -> ExportHealth {
+        self.tracker.health()
+    }
+}
+
+impl Default for WasiAsyncLogExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LogExporter for WasiAsyncLogExporter {
+    fn export(&self, batch: LogBatch<'_>) -> impl Future<Output = OTelSdkResult> + Send {
+        let logs = (!self.is_shutdown.load(Ordering::Relaxed)).then(|| {
+            batch
+                .iter()
+                .map(|(record, scope)| {
+                    to_wasi_limited_log_record(
+                        record,
+                        scope,
+                        self.resource.as_ref(),
+                        self.redactor.as_ref(),
+                        &self.limits,
+                    )
+                })
+                .collect::<Vec<_>>()
+        });
+        async move {
+            let Some(logs) = logs else {
+                return Err(OTelSdkError::AlreadyShutdown);
+            };
+            if logs.is_empty() {
+                return Ok(());
+            }
+            match AssertSend::new(export_logs(logs)).await {
+                Ok(()) => {
+                    self.tracker.record_success();
+                    Ok(())
+                }
+                Err(e) => {
+                    self.tracker.record_failure(&e);
+                    otel_error!(name: "export_logs_internal_error", msg = format!("Operation failed due to an internal error: {}", e));
+                    Err(OTelSdkError::InternalFailure(e))
+                }
+            }
+        }
+    }
+
+    fn shutdown_with_timeout(&self, _timeout: std::time::Duration) -> OTelSdkResult {
+        if self.is_shutdown.swap(true, Ordering::Relaxed) {
+            return Err(OTelSdkError::AlreadyShutdown);
+        }
+        Ok(())
+    }
+
+    fn set_resource(&mut self, resource: &opentelemetry_sdk::Resource) {
+        self.resource = Some(resource.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording_host::{set_export_error, take_log_batches};
+    use opentelemetry::{
+        logs::{AnyValue, LogRecord, Logger, LoggerProvider},
+        InstrumentationScope,
+    };
+    use opentelemetry_sdk::logs::SdkLoggerProvider;
+    use std::{
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    fn export(exporter: &WasiAsyncLogExporter, body: &'static str) -> OTelSdkResult {
+        let mut record = SdkLoggerProvider::builder()
+            .build()
+            .logger("test")
+            .create_log_record();
+        record.set_body(AnyValue::from(body));
+        let scope = InstrumentationScope::builder("test").build();
+        let mut cx = Context::from_waker(Waker::noop());
+        let batch = [(&record, &scope)];
+        let mut export = pin!(exporter.export(LogBatch::new(&batch)));
+        match export.as_mut().poll(&mut cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => panic!("export should complete without waiting"),
+        }
+    }
+
+    #[test]
+    fn exports_logs_and_tracks_health() {
+        let exporter = WasiAsyncLogExporter::new();
+
+        export(&exporter, "hello").unwrap();
+        let exports = take_log_batches();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0][0].log_record.body.as_deref(), Some("\"hello\""));
+
+        set_export_error(Some("host is full"));
+        assert!(export(&exporter, "hello").is_err());
+        set_export_error(None);
+        let health = exporter.health();
+        assert_eq!(health.successful_exports(), 1);
+        assert_eq!(health.failed_exports(), 1);
+        assert_eq!(health.last_error(), Some("host is full"));
+
+        exporter.shutdown().unwrap();
+        assert!(matches!(
+            export(&exporter, "hello"),
+            Err(OTelSdkError::AlreadyShutdown)
+        ));
+        assert!(take_log_batches().is_empty());
+    }
+
+    #[test]
+    fn skip_empty_batches() {
+        let exporter = WasiAsyncLogExporter::new();
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let result = match pin!(exporter.export(LogBatch::new(&[]))).poll(&mut cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => panic!("export should complete without waiting"),
+        };
+
+        assert!(result.is_ok());
+        assert!(take_log_batches().is_empty());
+        assert_eq!(exporter.health(), ExportHealth::default());
+    }
+}
@@ -1,48 +1,109 @@
-use crate::{serialize_seq, types::to_json, wit::wasi::otel::logs::*};
+#[cfg(feature = "batch-export")]
+use crate::wit::batch_export::wasi::otel::batch_export::LimitedLogRecord;
+use crate::{serialize_seq, types::to_json, wit::wasi::otel::logs::*, PayloadLimits, Redactor};
 use base64::Engine;
 use serde::{
     ser::{SerializeMap, SerializeSeq},
     Serialize,
 };
+use std::borrow::Cow;
 
+/// Converts a log record, applying the redactor and limits.
+///
+/// `log-record` has no count of dropped attributes, so the number of attributes dropped by the
+/// limits is not reported to the host.
 pub fn to_wasi_log_record(
     record: &opentelemetry_sdk::logs::SdkLogRecord,
     scope: &opentelemetry::InstrumentationScope,
     resource: Option<&opentelemetry_sdk::Resource>,
+    redactor: Option<&Redactor>,
+    limits: &PayloadLimits,
 ) -> LogRecord {
+    convert_log_record(record, scope, resource, redactor, limits).0
+}
+
+/// Converts a log record, applying the redactor and limits, along with the number of attributes
+/// dropped by the limits.
+#[cfg(feature = "batch-export")]
+pub fn to_wasi_limited_log_record(
+    record: &opentelemetry_sdk::logs::SdkLogRecord,
+    scope: &opentelemetry::InstrumentationScope,
+    resource: Option<&opentelemetry_sdk::Resource>,
+    redactor: Option<&Redactor>,
+    limits: &PayloadLimits,
+) -> LimitedLogRecord {
+    let (log_record, dropped_attributes) =
+        convert_log_record(record, scope, resource, redactor, limits);
+    LimitedLogRecord {
+        log_record,
+        dropped_attributes,
+    }
+}
+
+fn convert_log_record(
+    record: &opentelemetry_sdk::logs::SdkLogRecord,
+    scope: &opentelemetry::InstrumentationScope,
+    resource: Option<&opentelemetry_sdk::Resource>,
+    redactor: Option<&Redactor>,
+    limits: &PayloadLimits,
+) -> (LogRecord, u32) {
     let (trace_id, span_id, trace_flags) = record
         .trace_context()
         .as_ref()
         .map(|tc| {
             (
-                format!("{:x}", tc.trace_id).into(),
-                format!("{:x}", tc.span_id).into(),
+                tc.trace_id.to_string().into(),
+                tc.span_id.to_string().into(),
                 tc.trace_flags.map(Into::into),
             )
         })
         .unwrap_or((None, None, None));
 
-    LogRecord {
+    let mut dropped_attributes = 0;
+    let attributes = record
+        .attributes_iter()
+        .filter_map(|(k, v)| match redactor {
+            Some(redactor) => Some((k, Cow::Owned(redactor.redact_log_attribute(k, v)?))),
+            None => Some((k, Cow::Borrowed(v))),
+        })
+        .enumerate()
+        .filter_map(|(i, (k, v))| {
+            if !limits.is_attribute_within_limit(i) {
+                dropped_attributes += 1;
+                return None;
+            }
+            Some((k, limits.limit_any_value(&v).as_ref()).into())
+        })
+        .collect();
+
+    let log_record = LogRecord {
         timestamp: record.timestamp().map(Into::into),
-        body: record.body().map(|e| to_json(&AnyValueWrapper(e))),
+        body: record.body().map(|e| {
+            let body = match redactor {
+                Some(redactor) => Cow::Owned(redactor.redact_any_value(e)),
+                None => Cow::Borrowed(e),
+            };
+            to_json(&AnyValueWrapper(&limits.limit_any_value(&body)))
+        }),
         event_name: record.event_name().map(|e| e.to_string()),
         observed_timestamp: record.observed_timestamp().map(Into::into),
         severity_text: record.severity_text().map(|e| e.to_string()),
         severity_number: record.severity_number().map(|e| e as u8),
-        attributes: record.attributes_iter().map(|e| Some(e.into())).collect(),
+        attributes: Some(attributes),
         instrumentation_scope: Some(scope.into()),
         resource: resource.map(Into::into),
         trace_id,
         span_id,
         trace_flags,
-    }
+    };
+    (log_record, dropped_attributes)
 }
 
-impl From<&(opentelemetry::Key, opentelemetry::logs::AnyValue)> for KeyValue {
-    fn from(value: &(opentelemetry::Key, opentelemetry::logs::AnyValue)) -> Self {
+impl From<(&opentelemetry::Key, &opentelemetry::logs::AnyValue)> for KeyValue {
+    fn from(value: (&opentelemetry::Key, &opentelemetry::logs::AnyValue)) -> Self {
         Self {
             key: value.0.to_string(),
-            value: to_json(&AnyValueWrapper(&value.1)),
+            value: to_json(&AnyValueWrapper(value.1)),
         }
     }
 }
@@ -140,4 +201,55 @@ mod tests {
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn trace_context_ids_are_zero_padded() {
+        use opentelemetry::{
+            logs::{LogRecord, Logger, LoggerProvider},
+            trace::{SpanId, TraceId},
+        };
+
+        let logger = opentelemetry_sdk::logs::SdkLoggerProvider::builder()
+            .build()
+            .logger("test");
+        let mut record = logger.create_log_record();
+        record.set_trace_context(
+            TraceId::from(0x0af7651916cd43dd8448eb211c80319c),
+            SpanId::from(0x00f067aa0ba902b7),
+            None,
+        );
+        let log_record = to_wasi_log_record(
+            &record,
+            &opentelemetry::InstrumentationScope::builder("test").build(),
+            None,
+            None,
+            &PayloadLimits::default(),
+        );
+        assert_eq!(
+            log_record.trace_id.as_deref(),
+            Some("0af7651916cd43dd8448eb211c80319c")
+        );
+        assert_eq!(log_record.span_id.as_deref(), Some("00f067aa0ba902b7"));
+    }
+
+    #[cfg(feature = "batch-export")]
+    #[test]
+    fn count_attributes_dropped_by_limits() {
+        use opentelemetry::logs::{LogRecord, Logger, LoggerProvider};
+
+        let logger = opentelemetry_sdk::logs::SdkLoggerProvider::builder()
+            .build()
+            .logger("test");
+        let mut record = logger.create_log_record();
+        record.add_attributes([("a", 1), ("b", 2), ("c", 3)]);
+        let limited = to_wasi_limited_log_record(
+            &record,
+            &opentelemetry::InstrumentationScope::builder("test").build(),
+            None,
+            None,
+            &PayloadLimits::builder().with_max_attributes(1).build(),
+        );
+        assert_eq!(limited.log_record.attributes.unwrap().len(), 1);
+        assert_eq!(limited.dropped_attributes, 2);
+    }
 }
@@ -0,0 +1,176 @@
+use opentelemetry::{
+    logs::{AnyValue, LogRecord, Logger, Severity},
+    trace::TraceContextExt,
+    Context, Key,
+};
+use std::{collections::HashMap, error::Error};
+
+/// A named event emitted as a log record, such as an `exception` or a custom business event.
+///
+/// Events are emitted through a `Logger` from an `SdkLoggerProvider`, so they reach the host
+/// through the provider's `WasiLogProcessor`. The body is sent as a structured map and the record
+/// is linked to the current span when one is active.
+///
+/// # Example
+/// ```ignore
+/// let logger = logger_provider.logger("checkout");
+/// WasiEvent::builder("checkout.completed")
+///     .with_severity(Severity::Info)
+///     .with_body_field("order.id", "1234")
+///     .with_body_field("order.total", 42.5)
+///     .with_attribute("payment.method", "card")
+///     .build()
+///     .emit(&logger);
+/// ```
+#[derive(Debug, Clone)]
+pub struct WasiEvent {
+    name: &'static str,
+    severity: Option<Severity>,
+    body: HashMap<Key, AnyValue>,
+    attributes: Vec<(Key, AnyValue)>,
+}
+
+#[derive(Debug)]
+pub struct WasiEventBuilder {
+    event: WasiEvent,
+}
+
+impl WasiEventBuilder {
+    /// Set the severity of the event.
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.event.severity = Some(severity);
+        self
+    }
+
+    /// Add a field to the event's body. Use `AnyValue::Map` values to nest fields.
+    pub fn with_body_field(mut self, key: impl Into<Key>, value: impl Into<AnyValue>) -> Self {
+        self.event.body.insert(key.into(), value.into());
+        self
+    }
+
+    /// Add an attribute to the event's log record.
+    pub fn with_attribute(mut self, key: impl Into<Key>, value: impl Into<AnyValue>) -> Self {
+        self.event.attributes.push((key.into(), value.into()));
+        self
+    }
+
+    /// Build the event.
+    pub fn build(self) -> WasiEvent {
+        self.event
+    }
+}
+
+impl WasiEvent {
+    /// Create a new builder for configuring a WasiEvent named `name`.
+    pub fn builder(name: &'static str) -> WasiEventBuilder {
+        WasiEventBuilder {
+            event: WasiEvent {
+                name,
+                severity: None,
+                body: HashMap::new(),
+                attributes: Vec::new(),
+            },
+        }
+    }
+
+    /// Create an `exception` event following the OpenTelemetry semantic conventions.
+    pub fn exception<E: Error>(error: &E) -> Self {
+        Self::builder("exception")
+            .with_severity(Severity::Error)
+            .with_attribute("exception.message", error.to_string())
+            .with_attribute("exception.type", std::any::type_name::<E>())
+            .build()
+    }
+
+    /// Emit the event through `logger`.
+    pub fn emit<L: Logger>(self, logger: &L) {
+        let mut record = logger.create_log_record();
+        self.fill_record(&mut record);
+        logger.emit(record);
+    }
+
+    fn fill_record<R: LogRecord>(self, record: &mut R) {
+        record.set_event_name(self.name);
+        if let Some(severity) = self.severity {
+            record.set_severity_number(severity);
+            record.set_severity_text(severity.name());
+        }
+        if !self.body.is_empty() {
+            record.set_body(AnyValue::Map(Box::new(self.body)));
+        }
+        record.add_attributes(self.attributes);
+        Context::map_current(|cx| {
+            if cx.has_active_span() {
+                let span_context = cx.span().span_context().clone();
+                record.set_trace_context(
+                    span_context.trace_id(),
+                    span_context.span_id(),
+                    Some(span_context.trace_flags()),
+                );
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{logs::conversion::to_wasi_log_record, PayloadLimits};
+    use opentelemetry::{
+        logs::LoggerProvider,
+        trace::{SpanContext, SpanId, TraceFlags, TraceId, TraceState},
+        InstrumentationScope,
+    };
+    use opentelemetry_sdk::logs::SdkLoggerProvider;
+
+    #[test]
+    fn event_body_stays_nested_map() {
+        let logger = SdkLoggerProvider::builder().build().logger("test");
+        let mut record = logger.create_log_record();
+        let mut customer: HashMap<Key, AnyValue> = HashMap::new();
+        customer.insert(Key::new("id"), AnyValue::Int(7));
+        customer.insert(
+            Key::new("tags"),
+            AnyValue::ListAny(Box::new(vec!["vip".into()])),
+        );
+        let span_context = SpanContext::new(
+            TraceId::from(0x4bf92f3577b34da6a3ce929d0e0e4736),
+            SpanId::from(0xb7ad6b7169203331),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        );
+        let cx = Context::current().with_remote_span_context(span_context);
+        let _guard = cx.attach();
+
+        WasiEvent::builder("checkout.completed")
+            .with_severity(Severity::Info)
+            .with_body_field("order.id", "1234")
+            .with_body_field("customer", AnyValue::Map(Box::new(customer)))
+            .build()
+            .fill_record(&mut record);
+        let log_record = to_wasi_log_record(
+            &record,
+            &InstrumentationScope::builder("test").build(),
+            None,
+            None,
+            &PayloadLimits::default(),
+        );
+
+        assert_eq!(log_record.event_name.as_deref(), Some("checkout.completed"));
+        assert_eq!(log_record.severity_number, Some(Severity::Info as u8));
+        assert_eq!(
+            log_record.trace_id.as_deref(),
+            Some("4bf92f3577b34da6a3ce929d0e0e4736")
+        );
+        assert_eq!(log_record.span_id.as_deref(), Some("b7ad6b7169203331"));
+        let body: serde_json::Value = serde_json::from_str(&log_record.body.unwrap()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "order.id": "1234",
+                "customer": { "id": 7, "tags": ["vip"] }
+            })
+        );
+    }
+}
@@ -0,0 +1,363 @@
+#[cfg(feature = "batch-export")]
+use crate::logs::conversion::to_wasi_limited_log_record;
+#[cfg(all(test, feature = "batch-export"))]
+use crate::recording_host::export_logs;
+#[cfg(test)]
+use crate::recording_host::on_emit;
+#[cfg(all(not(test), feature = "batch-export"))]
+use crate::wit::batch_export::wasi::otel::batch_export::export_logs;
+#[cfg(not(test))]
+use crate::wit::wasi::otel::logs::on_emit;
+use crate::{
+    health::{ExportTracker, Signal},
+    logs::conversion::to_wasi_log_record,
+    ExportHealth, ExportHealthHandle, PayloadLimits, Redactor,
+};
+#[cfg(any(feature = "batch-export", feature = "otlp"))]
+use opentelemetry::otel_error;
+use opentelemetry_sdk::{
+    error::{OTelSdkError, OTelSdkResult},
+    logs::{LogBatch, LogExporter},
+};
+use std::{
+    future::Future,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// A log exporter that sends log records to a WASI host.
+///
+/// This allows the WASI transport to be combined with the SDK's log processors or custom
+/// processors, such as filters and enrichers. Log records are sent to the host synchronously, so
+/// the returned future is always ready. The resource is provided by the `SdkLoggerProvider` the
+/// exporter is registered with, unless one is set on the builder.
+///
+/// # Example
+/// ```ignore
+/// let provider = SdkLoggerProvider::builder()
+///     .with_log_processor(SimpleLogProcessor::new(WasiLogExporter::new()))
+///     .build();
+/// ```
+#[derive(Debug)]
+pub struct WasiLogExporter {
+    is_shutdown: AtomicBool,
+    tracker: ExportTracker,
+    resource: Option<opentelemetry_sdk::Resource>,
+    redactor: Option<Redactor>,
+    limits: PayloadLimits,
+    #[cfg(feature = "batch-export")]
+    batch_export: bool,
+    #[cfg(feature = "otlp")]
+    otlp_encoding: bool,
+}
+
+#[derive(Default)]
+pub struct WasiLogExporterBuilder {
+    resource: Option<opentelemetry_sdk::Resource>,
+    redactor: Option<Redactor>,
+    limits: PayloadLimits,
+    #[cfg(feature = "batch-export")]
+    batch_export: bool,
+    #[cfg(feature = "otlp")]
+    otlp_encoding: bool,
+    health: ExportHealthHandle,
+}
+
+impl WasiLogExporterBuilder {
+    /// Attach a resource to every log record sent to the host.
+    pub fn with_resource(mut self, resource: opentelemetry_sdk::Resource) -> Self {
+        self.resource = Some(resource);
+        self
+    }
+
+    /// Redact log bodies and attributes before they are sent to the host.
+    #[cfg(feature = "redaction")]
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+
+    /// Truncate log attributes and bodies that exceed `limits`.
+    ///
+    /// `wasi:otel/logs.on-emit` has no field for the number of attributes dropped, so it is only
+    /// reported to the host with batch export or OTLP encoding enabled.
+    pub fn with_limits(mut self, limits: PayloadLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Send each batch of log records to the host in a single call to `wasi:otel/batch-export`
+    /// instead of calling `wasi:otel/logs.on-emit` for every record.
+    ///
+    /// Only enable this if the host implements the `wasi:otel/imports-with-batch-export` world.
+    #[cfg(feature = "batch-export")]
+    pub fn with_batch_export(mut self) -> Self {
+        self.batch_export = true;
+        self
+    }
+
+    /// Send log records to the host as an encoded OTLP `ExportLogsServiceRequest` through the
+    /// `wasi:otel/otlp` interface instead of as WIT records.
+    ///
+    /// The redactor and limits are still applied before encoding, and attributes dropped by the
+    /// limits are counted in the records' `dropped_attributes_count`.
+    #[cfg(feature = "otlp")]
+    pub fn with_otlp_encoding(mut self) -> Self {
+        self.otlp_encoding = true;
+        self
+    }
+
+    /// Returns a handle for reading the health of the exporter once it is built, even after it is
+    /// moved into a provider or processor.
+    pub fn health_handle(&self) -> ExportHealthHandle {
+        self.health.clone()
+    }
+
+    /// Build the exporter.
+    pub fn build(self) -> WasiLogExporter {
+        WasiLogExporter {
+            is_shutdown: AtomicBool::new(false),
+            tracker: ExportTracker::new(Signal::Logs, self.health),
+            resource: self.resource,
+            redactor: self.redactor,
+            limits: self.limits,
+            #[cfg(feature = "batch-export")]
+            batch_export: self.batch_export,
+            #[cfg(feature = "otlp")]
+            otlp_encoding: self.otlp_encoding,
+        }
+    }
+}
+
+impl WasiLogExporter {
+    /// Create a new `WasiLogExporter`.
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    /// Create a new builder for configuring a WasiLogExporter.
+    pub fn builder() -> WasiLogExporterBuilder {
+        WasiLogExporterBuilder::default()
+    }
+
+    /// Sends a batch of log records to the host.
+    pub(crate) fn export_batch(&self, batch: LogBatch<'_>) -> OTelSdkResult {
+        if self.is_shutdown.load(Ordering::Relaxed) {
+            return Err(OTelSdkError::AlreadyShutdown);
+        }
+        #[cfg(feature = "otlp")]
+        if self.otlp_encoding {
+            let request = crate::otlp::encode_logs(
+                batch,
+                self.resource.as_ref(),
+                self.redactor.as_ref(),
+                &self.limits,
+            );
+            return self.record(crate::wit::otlp::wasi::otel::otlp::export_logs(&request));
+        }
+        #[cfg(feature = "batch-export")]
+        if self.batch_export {
+            let logs: Vec<_> = batch
+                .iter()
+                .map(|(record, scope)| {
+                    to_wasi_limited_log_record(
+                        record,
+                        scope,
+                        self.resource.as_ref(),
+                        self.redactor.as_ref(),
+                        &self.limits,
+                    )
+                })
+                .collect();
+            return self.record(export_logs(&logs));
+        }
+        // `on-emit` does not report whether the host accepted the record, so no outcome is recorded.
+        for (record, scope) in batch.iter() {
+            on_emit(&to_wasi_log_record(
+                record,
+                scope,
+                self.resource.as_ref(),
+                self.redactor.as_ref(),
+                &self.limits,
+            ));
+        }
+        Ok(())
+    }
+
+    #[cfg(any(feature = "batch-export", feature = "otlp"))]
+    fn record(&self, result: Result<(), String>) -> OTelSdkResult {
+        match result {
+            Ok(()) => {
+                self.tracker.record_success();
+                Ok(())
+            }
+            Err(e) => {
+                self.tracker.record_failure(&e);
+                otel_error!(name: "export_logs_internal_error", msg = format!("Operation failed due to an internal error: {}", e));
+                Err(OTelSdkError::InternalFailure(e))
+            }
+        }
+    }
+
+    /// Returns how exports to the host have fared so far.
+    pub fn health(&self) -> ExportHealth {
+        self.tracker.health()
+    }
+
+    /// Returns an error if any export failed since the last flush.
+    pub(crate) fn flush(&self) -> OTelSdkResult {
+        if self.is_shutdown.load(Ordering::Relaxed) {
+            return Err(OTelSdkError::AlreadyShutdown);
+        }
+        match self.tracker.take_flush_error() {
+            Some(e) => Err(OTelSdkError::InternalFailure(e)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Default for WasiLogExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LogExporter for WasiLogExporter {
+    fn export(&self, batch: LogBatch<'_>) -> impl Future<Output = OTelSdkResult> + Send {
+        std::future::ready(self.export_batch(batch))
+    }
+
+    fn shutdown_with_timeout(&self, _timeout: std::time::Duration) -> OTelSdkResult {
+        if self.is_shutdown.swap(true, Ordering::Relaxed) {
+            return Err(OTelSdkError::AlreadyShutdown);
+        }
+        Ok(())
+    }
+
+    fn set_resource(&mut self, resource: &opentelemetry_sdk::Resource) {
+        if self.resource.is_none() {
+            self.resource = Some(resource.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording_host::take_logs;
+    use opentelemetry::{
+        logs::{LogRecord as _, Logger, LoggerProvider},
+        InstrumentationScope,
+    };
+    use opentelemetry_sdk::logs::{SdkLogRecord, SdkLoggerProvider};
+    use std::{
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    fn export(exporter: &WasiLogExporter, record: &SdkLogRecord) -> OTelSdkResult {
+        let scope = InstrumentationScope::builder("test").build();
+        let batch = [(record, &scope)];
+        let export = pin!(exporter.export(LogBatch::new(&batch)));
+        let result = match export.poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(result) => result,
+            Poll::Pending => panic!("export should complete without waiting"),
+        };
+        result
+    }
+
+    fn record(body: &'static str) -> SdkLogRecord {
+        let mut record = SdkLoggerProvider::builder()
+            .build()
+            .logger("test")
+            .create_log_record();
+        record.set_body(body.into());
+        record.add_attribute("password", "hunter2");
+        record
+    }
+
+    #[test]
+    fn exports_log_records_until_shutdown() {
+        let builder = WasiLogExporter::builder().with_resource(
+            opentelemetry_sdk::Resource::builder_empty()
+                .with_service_name("checkout")
+                .build(),
+        );
+        #[cfg(feature = "redaction")]
+        let builder =
+            builder.with_redactor(Redactor::builder().with_denied_keys(["password"]).build());
+        let exporter = builder.build();
+
+        export(&exporter, &record("paid")).unwrap();
+        let logs = take_logs();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].body.as_deref(), Some("\"paid\""));
+        #[cfg(feature = "redaction")]
+        assert_eq!(logs[0].attributes.as_ref().map(Vec::len), Some(0));
+        assert_eq!(
+            logs[0].resource.as_ref().unwrap().attributes[0].key,
+            "service.name"
+        );
+        assert_eq!(exporter.health(), ExportHealth::default());
+
+        exporter.shutdown_with_timeout(Default::default()).unwrap();
+        assert!(matches!(
+            export(&exporter, &record("refunded")),
+            Err(OTelSdkError::AlreadyShutdown)
+        ));
+        assert!(take_logs().is_empty());
+        assert!(matches!(
+            exporter.flush(),
+            Err(OTelSdkError::AlreadyShutdown)
+        ));
+        assert!(matches!(
+            exporter.shutdown_with_timeout(Default::default()),
+            Err(OTelSdkError::AlreadyShutdown)
+        ));
+    }
+
+    #[test]
+    fn prefer_the_builder_resource_over_the_provider_resource() {
+        let provider_resource = opentelemetry_sdk::Resource::builder_empty()
+            .with_attribute(opentelemetry::KeyValue::new("host.name", "edge"))
+            .build();
+        let mut exporter = WasiLogExporter::builder()
+            .with_resource(
+                opentelemetry_sdk::Resource::builder_empty()
+                    .with_service_name("checkout")
+                    .build(),
+            )
+            .build();
+        exporter.set_resource(&provider_resource);
+        let mut default_exporter = WasiLogExporter::new();
+        default_exporter.set_resource(&provider_resource);
+
+        export(&exporter, &record("paid")).unwrap();
+        export(&default_exporter, &record("paid")).unwrap();
+        let logs = take_logs();
+        assert_eq!(
+            logs[0].resource.as_ref().unwrap().attributes[0].key,
+            "service.name"
+        );
+        assert_eq!(
+            logs[1].resource.as_ref().unwrap().attributes[0].key,
+            "host.name"
+        );
+    }
+
+    #[cfg(feature = "batch-export")]
+    #[test]
+    fn report_rejected_batches() {
+        let exporter = WasiLogExporter::builder().with_batch_export().build();
+
+        crate::recording_host::set_export_error(Some("host is full"));
+        let result = export(&exporter, &record("paid"));
+        crate::recording_host::set_export_error(None);
+
+        assert!(matches!(result, Err(OTelSdkError::InternalFailure(_))));
+        assert!(crate::recording_host::take_log_batches().is_empty());
+        assert_eq!(exporter.health().failed_exports(), 1);
+        assert_eq!(exporter.health().last_error(), Some("host is full"));
+        assert!(exporter.flush().is_err());
+        assert!(exporter.flush().is_ok());
+    }
+}
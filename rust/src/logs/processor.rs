@@ -1,18 +1,206 @@
-use crate::{logs::conversion::to_wasi_log_record, wit::wasi};
-use opentelemetry_sdk::error::OTelSdkResult;
-use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(test)]
+use crate::recording_host::outer_span_context;
+#[cfg(not(test))]
+use crate::wit::wasi::otel::tracing::outer_span_context;
+#[cfg(feature = "redaction")]
+use crate::Redactor;
+use crate::{
+    ExportHealth, ExportHealthHandle, PayloadLimits, WasiLogExporter, WasiLogExporterBuilder,
+};
+#[cfg(feature = "batch-export")]
+use opentelemetry::InstrumentationScope;
+use opentelemetry::{
+    logs::{LogRecord, Severity},
+    trace::{SpanContext, TraceContextExt},
+    Context,
+};
+#[cfg(feature = "batch-export")]
+use opentelemetry_sdk::logs::SdkLogRecord;
+use opentelemetry_sdk::{
+    error::OTelSdkResult,
+    logs::{LogBatch, LogExporter},
+};
+#[cfg(feature = "batch-export")]
+use std::sync::Mutex;
 
+/// The number of log records buffered for batch export before they are exported without waiting
+/// for a flush.
+#[cfg(feature = "batch-export")]
+const MAX_BATCH_SIZE: usize = 512;
+
+/// A log processor that sends every log record to the WASI host as soon as it is emitted.
+///
+/// This is a thin wrapper around a [`WasiLogExporter`] that additionally filters records by
+/// severity and links records to the active span. With batch export enabled, records are buffered
+/// instead and exported together when the provider is flushed or shut down.
 #[derive(Debug)]
 pub struct WasiLogProcessor {
-    is_shutdown: AtomicBool,
-    resource: Option<opentelemetry_sdk::Resource>,
+    exporter: WasiLogExporter,
+    min_severity: Option<Severity>,
+    trace_context_injection: TraceContextInjection,
+    #[cfg(feature = "batch-export")]
+    batch: Option<Mutex<Vec<(SdkLogRecord, InstrumentationScope)>>>,
+}
+
+/// Where the `WasiLogProcessor` takes the trace context of log records that have none.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TraceContextInjection {
+    /// Leave log records without a trace context unlinked.
+    Disabled,
+    /// Use the span active in `Context::current()`.
+    Current,
+    /// Use the span active in `Context::current()`, falling back to the host's outer span.
+    ///
+    /// The host's outer span is read for every record emitted without an active span, so a
+    /// processor that outlives a request links each record to the request the host is handling.
+    #[default]
+    CurrentOrHost,
+}
+
+impl TraceContextInjection {
+    fn span_context(self, host: impl FnOnce() -> Option<SpanContext>) -> Option<SpanContext> {
+        if self == Self::Disabled {
+            return None;
+        }
+        let current = Context::map_current(|cx| {
+            cx.has_active_span()
+                .then(|| cx.span().span_context().clone())
+        });
+        match (current, self) {
+            (Some(span_context), _) => Some(span_context),
+            (None, Self::CurrentOrHost) => host(),
+            (None, _) => None,
+        }
+    }
+}
+
+/// Reads the host's outer span, if it has one.
+fn host_span_context() -> Option<SpanContext> {
+    outer_span_context()
+        .try_into()
+        .ok()
+        .filter(SpanContext::is_valid)
+}
+
+#[derive(Default)]
+pub struct WasiLogProcessorBuilder {
+    exporter: WasiLogExporterBuilder,
+    min_severity: Option<Severity>,
+    trace_context_injection: TraceContextInjection,
+    #[cfg(feature = "batch-export")]
+    batch_export: bool,
+}
+
+impl WasiLogProcessorBuilder {
+    /// Attach a resource to every log record sent to the host.
+    pub fn with_resource(mut self, resource: opentelemetry_sdk::Resource) -> Self {
+        self.exporter = self.exporter.with_resource(resource);
+        self
+    }
+
+    /// Redact log bodies and attributes before they are sent to the host.
+    #[cfg(feature = "redaction")]
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.exporter = self.exporter.with_redactor(redactor);
+        self
+    }
+
+    /// Truncate log attributes and bodies that exceed `limits`.
+    ///
+    /// `wasi:otel/logs.on-emit` has no field for the number of attributes dropped, so it is only
+    /// reported to the host with batch export or OTLP encoding enabled.
+    pub fn with_limits(mut self, limits: PayloadLimits) -> Self {
+        self.exporter = self.exporter.with_limits(limits);
+        self
+    }
+
+    /// Drop log records with a severity lower than `severity` instead of sending them to the host.
+    ///
+    /// Records without a severity are always sent.
+    pub fn with_min_severity(mut self, severity: Severity) -> Self {
+        self.min_severity = Some(severity);
+        self
+    }
+
+    /// Send log records to the host through `wasi:otel/batch-export` instead of
+    /// `wasi:otel/logs.on-emit`.
+    ///
+    /// Log records are buffered and exported in a single call when the provider is flushed or
+    /// shut down, or once 512 records have accumulated. Call `force_flush` on the provider at the
+    /// end of each request so records aren't held back.
+    ///
+    /// Only enable this if the host implements the `wasi:otel/imports-with-batch-export` world.
+    #[cfg(feature = "batch-export")]
+    pub fn with_batch_export(mut self) -> Self {
+        self.exporter = self.exporter.with_batch_export();
+        self.batch_export = true;
+        self
+    }
+
+    /// Send log records to the host as an encoded OTLP `ExportLogsServiceRequest` through the
+    /// `wasi:otel/otlp` interface instead of as WIT records.
+    #[cfg(feature = "otlp")]
+    pub fn with_otlp_encoding(mut self) -> Self {
+        self.exporter = self.exporter.with_otlp_encoding();
+        self
+    }
+
+    /// Set where the trace context of log records without one is taken from.
+    ///
+    /// By default, records are linked to the span active in `Context::current()`, or else to the
+    /// host's outer span. Records that already have a trace context are left unchanged.
+    pub fn with_trace_context_injection(mut self, injection: TraceContextInjection) -> Self {
+        self.trace_context_injection = injection;
+        self
+    }
+
+    /// Returns a handle for reading the health of the processor once it is built, even after it
+    /// is moved into a provider.
+    pub fn health_handle(&self) -> ExportHealthHandle {
+        self.exporter.health_handle()
+    }
+
+    /// Build the processor.
+    pub fn build(self) -> WasiLogProcessor {
+        WasiLogProcessor {
+            exporter: self.exporter.build(),
+            min_severity: self.min_severity,
+            trace_context_injection: self.trace_context_injection,
+            #[cfg(feature = "batch-export")]
+            batch: self.batch_export.then(Mutex::default),
+        }
+    }
 }
 
 impl WasiLogProcessor {
     pub fn new(resource: Option<opentelemetry_sdk::Resource>) -> Self {
-        Self {
-            is_shutdown: AtomicBool::new(false),
-            resource,
+        let mut builder = Self::builder();
+        if let Some(resource) = resource {
+            builder = builder.with_resource(resource);
+        }
+        builder.build()
+    }
+
+    /// Returns how exports to the host have fared so far.
+    pub fn health(&self) -> ExportHealth {
+        self.exporter.health()
+    }
+
+    /// Create a new builder for configuring a WasiLogProcessor.
+    pub fn builder() -> WasiLogProcessorBuilder {
+        WasiLogProcessorBuilder::default()
+    }
+
+    /// Exports the buffered log records, if any.
+    #[cfg(feature = "batch-export")]
+    fn export_buffered(&self) {
+        let Some(batch) = &self.batch else {
+            return;
+        };
+        let records = std::mem::take(&mut *batch.lock().unwrap());
+        if !records.is_empty() {
+            let records: Vec<_> = records.iter().map(|(data, scope)| (data, scope)).collect();
+            _ = self.exporter.export_batch(LogBatch::new(&records));
         }
     }
 }
@@ -23,21 +211,202 @@ impl opentelemetry_sdk::logs::LogProcessor for WasiLogProcessor {
         data: &mut opentelemetry_sdk::logs::SdkLogRecord,
         scope: &opentelemetry::InstrumentationScope,
     ) {
-        wasi::otel::logs::on_emit(&to_wasi_log_record(data, scope, self.resource.as_ref()))
+        if let (Some(min), Some(severity)) = (self.min_severity, data.severity_number()) {
+            if (severity as u8) < (min as u8) {
+                return;
+            }
+        }
+        if data.trace_context().is_none() {
+            if let Some(span_context) = self.trace_context_injection.span_context(host_span_context)
+            {
+                data.set_trace_context(
+                    span_context.trace_id(),
+                    span_context.span_id(),
+                    Some(span_context.trace_flags()),
+                );
+            }
+        }
+        #[cfg(feature = "batch-export")]
+        if let Some(batch) = &self.batch {
+            let mut records = batch.lock().unwrap();
+            records.push((data.clone(), scope.clone()));
+            if records.len() >= MAX_BATCH_SIZE {
+                drop(records);
+                self.export_buffered();
+            }
+            return;
+        }
+        _ = self
+            .exporter
+            .export_batch(LogBatch::new(&[(&*data, scope)]));
     }
 
-    fn force_flush(&self) -> opentelemetry_sdk::error::OTelSdkResult {
-        if self.is_shutdown.load(Ordering::Relaxed) {
-            return OTelSdkResult::Err(opentelemetry_sdk::error::OTelSdkError::AlreadyShutdown);
-        }
-        Ok(())
+    fn force_flush(&self) -> OTelSdkResult {
+        #[cfg(feature = "batch-export")]
+        self.export_buffered();
+        self.exporter.flush()
     }
 
-    fn shutdown(&self) -> opentelemetry_sdk::error::OTelSdkResult {
+    fn shutdown(&self) -> OTelSdkResult {
         let result = self.force_flush();
-        if self.is_shutdown.swap(true, Ordering::Relaxed) {
-            return OTelSdkResult::Err(opentelemetry_sdk::error::OTelSdkError::AlreadyShutdown);
-        }
+        self.exporter.shutdown()?;
         result
     }
+
+    fn shutdown_with_timeout(&self, _timeout: std::time::Duration) -> OTelSdkResult {
+        self.shutdown()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording_host::take_logs;
+    use opentelemetry::{
+        logs::{AnyValue, Logger, LoggerProvider},
+        trace::{SpanId, TraceFlags, TraceId, TraceState},
+    };
+    use opentelemetry_sdk::logs::SdkLoggerProvider;
+
+    fn emit(builder: WasiLogProcessorBuilder, body: &'static str) {
+        let provider = SdkLoggerProvider::builder()
+            .with_log_processor(builder.build())
+            .build();
+        let logger = provider.logger("test");
+        let mut record = logger.create_log_record();
+        record.set_body(AnyValue::from(body));
+        logger.emit(record);
+    }
+
+    #[test]
+    fn emit_each_log_record() {
+        emit(WasiLogProcessor::builder(), "hello");
+        let logs = take_logs();
+
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].body.as_deref(), Some("\"hello\""));
+    }
+
+    #[cfg(feature = "batch-export")]
+    #[test]
+    fn read_health_once_the_provider_owns_the_processor() {
+        let builder = WasiLogProcessor::builder().with_batch_export();
+        let health = builder.health_handle();
+        emit(builder, "hello");
+        crate::recording_host::take_log_batches();
+
+        assert_eq!(health.health().successful_exports(), 1);
+        assert_eq!(health.health().failed_exports(), 0);
+    }
+
+    #[test]
+    fn record_no_health_for_unacknowledged_records() {
+        let builder = WasiLogProcessor::builder();
+        let health = builder.health_handle();
+        emit(builder, "hello");
+        take_logs();
+
+        assert_eq!(health.health(), ExportHealth::default());
+    }
+
+    #[cfg(feature = "batch-export")]
+    #[test]
+    fn export_log_records_in_a_batch_when_enabled() {
+        let provider = SdkLoggerProvider::builder()
+            .with_log_processor(WasiLogProcessor::builder().with_batch_export().build())
+            .build();
+        let logger = provider.logger("test");
+        for body in ["hello", "world"] {
+            let mut record = logger.create_log_record();
+            record.set_body(AnyValue::from(body));
+            logger.emit(record);
+        }
+        assert!(crate::recording_host::take_log_batches().is_empty());
+        provider.force_flush().unwrap();
+        let batches = crate::recording_host::take_log_batches();
+
+        assert!(take_logs().is_empty());
+        assert_eq!(batches.len(), 1);
+        let bodies: Vec<_> = batches[0]
+            .iter()
+            .map(|log| log.log_record.body.as_deref())
+            .collect();
+        assert_eq!(bodies, vec![Some("\"hello\""), Some("\"world\"")]);
+    }
+
+    #[test]
+    fn inject_current_span_context() {
+        let host = || panic!("the host should not be asked for its outer span");
+        assert_eq!(TraceContextInjection::Current.span_context(host), None);
+        assert_eq!(TraceContextInjection::Disabled.span_context(host), None);
+
+        let span_context = SpanContext::new(
+            TraceId::from(0x4bf92f3577b34da6a3ce929d0e0e4736),
+            SpanId::from(0x00f067aa0ba902b7),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        );
+        let _guard = Context::current()
+            .with_remote_span_context(span_context.clone())
+            .attach();
+        assert_eq!(
+            TraceContextInjection::Current.span_context(host),
+            Some(span_context.clone())
+        );
+        assert_eq!(
+            TraceContextInjection::CurrentOrHost.span_context(host),
+            Some(span_context)
+        );
+        assert_eq!(TraceContextInjection::Disabled.span_context(host), None);
+    }
+
+    #[test]
+    fn inject_the_host_span_context_of_each_request() {
+        let provider = SdkLoggerProvider::builder()
+            .with_log_processor(WasiLogProcessor::builder().build())
+            .build();
+        let logger = provider.logger("test");
+        for (trace_id, body) in [
+            (0x4bf92f3577b34da6a3ce929d0e0e4736, "hello"),
+            (0x0af7651916cd43dd8448eb211c80319c, "world"),
+        ] {
+            let span_context = SpanContext::new(
+                TraceId::from(trace_id),
+                SpanId::from(0xb7ad6b7169203331),
+                TraceFlags::SAMPLED,
+                true,
+                TraceState::default(),
+            );
+            crate::recording_host::set_outer_span_context(Some(span_context.into()));
+            let mut record = logger.create_log_record();
+            record.set_body(AnyValue::from(body));
+            logger.emit(record);
+        }
+        crate::recording_host::set_outer_span_context(None);
+        let logs = take_logs();
+
+        assert_eq!(crate::recording_host::take_outer_span_context_calls(), 2);
+        let trace_ids: Vec<_> = logs.iter().map(|log| log.trace_id.as_deref()).collect();
+        assert_eq!(
+            trace_ids,
+            vec![
+                Some("4bf92f3577b34da6a3ce929d0e0e4736"),
+                Some("0af7651916cd43dd8448eb211c80319c")
+            ]
+        );
+        assert!(logs
+            .iter()
+            .all(|log| log.span_id.as_deref() == Some("b7ad6b7169203331")));
+    }
+
+    #[test]
+    fn leave_records_unlinked_without_a_host_span() {
+        emit(WasiLogProcessor::builder(), "hello");
+        let logs = take_logs();
+
+        assert_eq!(crate::recording_host::take_outer_span_context_calls(), 1);
+        assert_eq!(logs[0].trace_id, None);
+        assert_eq!(logs[0].span_id, None);
+    }
 }
@@ -1,4 +1,39 @@
+#[cfg(all(
+    feature = "async-export",
+    any(test, all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+mod async_exporter;
 mod conversion;
+mod exemplar;
 mod exporter;
+mod push_exporter;
+mod retry;
+mod runtime;
+mod state;
+mod unchanged;
+mod view;
 
+#[cfg(all(
+    feature = "async-export",
+    any(test, all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+pub use async_exporter::WasiAsyncMetricExporter;
+#[cfg(all(
+    feature = "async-export",
+    any(test, all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+pub use async_exporter::WasiAsyncMetricExporterBuilder;
+pub use exemplar::ExemplarFilter;
+pub use exemplar::WasiMeterProvider;
 pub use exporter::WasiMetricExporter;
+pub use exporter::WasiMetricExporterBuilder;
+pub(crate) use exporter::WeakWasiMetricExporter;
+pub use push_exporter::WasiPushMetricExporter;
+pub use push_exporter::WasiPushMetricExporterBuilder;
+pub use retry::RetryBufferLimits;
+pub use retry::RetryBufferLimitsBuilder;
+pub use runtime::WasiRuntimeMetrics;
+pub use runtime::WasiTrackingAllocator;
+pub use state::MetricStateStore;
+pub use view::WasiMetricView;
+pub use view::WasiMetricViewBuilder;
@@ -0,0 +1,227 @@
+#[cfg(test)]
+use crate::recording_host::export_metrics_async as export_metrics;
+#[cfg(not(test))]
+use crate::wit::async_export::wasi::otel::async_export::export_metrics;
+use crate::{
+    async_support::AssertSend,
+    health::{record_dropped_metric_points, ExportTracker, Signal},
+    metrics::{conversion::to_wasi_resource_metrics, retry::data_point_count},
+    ExportHealth, ExportHealthHandle, PayloadLimits, Redactor,
+};
+use opentelemetry::otel_error;
+use opentelemetry_sdk::{
+    error::{OTelSdkError, OTelSdkResult},
+    metrics::{data::ResourceMetrics, exporter::PushMetricExporter, Temporality},
+};
+use std::{
+    future::Future,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// A push metric exporter that sends metrics to a WASI host without blocking.
+///
+/// This uses the asynchronous `wasi:otel/async-export` interface and is meant for wasip3
+/// components running on an async executor, where it can be driven by a periodic reader.
+/// Collections without any data points are not sent to the host.
+#[derive(Debug)]
+pub struct WasiAsyncMetricExporter {
+    is_shutdown: AtomicBool,
+    tracker: ExportTracker,
+    redactor: Option<Redactor>,
+    limits: PayloadLimits,
+    temporality: Temporality,
+}
+
+#[derive(Default)]
+pub struct WasiAsyncMetricExporterBuilder {
+    redactor: Option<Redactor>,
+    limits: PayloadLimits,
+    temporality: Temporality,
+    health: ExportHealthHandle,
+}
+
+impl WasiAsyncMetricExporterBuilder {
+    /// Redact data point and exemplar attributes before they are sent to the host.
+    #[cfg(feature = "redaction")]
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+
+    /// Truncate data point and exemplar attributes that exceed `limits`.
+    pub fn with_limits(mut self, limits: PayloadLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Set the temporality metrics are aggregated with. Defaults to cumulative.
+    pub fn with_temporality(mut self, temporality: Temporality) -> Self {
+        self.temporality = temporality;
+        self
+    }
+
+    /// Returns a handle for reading the health of the exporter once it is built, even after it is
+    /// moved into a reader.
+    pub fn health_handle(&self) -> ExportHealthHandle {
+        self.health.clone()
+    }
+
+    /// Build the exporter.
+    pub fn build(self) -> WasiAsyncMetricExporter {
+        WasiAsyncMetricExporter {
+            is_shutdown: AtomicBool::new(false),
+            tracker: ExportTracker::new(Signal::Metrics, self.health),
+            redactor: self.redactor,
+            limits: self.limits,
+            temporality: self.temporality,
+        }
+    }
+}
+
+impl WasiAsyncMetricExporter {
+    /// Create a new `WasiAsyncMetricExporter`.
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    /// Create a new builder for configuring a WasiAsyncMetricExporter.
+    pub fn builder() -> WasiAsyncMetricExporterBuilder {
+        WasiAsyncMetricExporterBuilder::default()
+    }
+
+    /// Returns how exports to the host have fared so far.
+    pub fn health(&self) -> ExportHealth {
+        self.tracker.health()
+    }
+}
+
+impl Default for WasiAsyncMetricExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PushMetricExporter for WasiAsyncMetricExporter {
+    fn export(&self, metrics: &ResourceMetrics) -> impl Future<Output = OTelSdkResult> + Send {
+        let metrics = (!self.is_shutdown.load(Ordering::Relaxed))
+            .then(|| to_wasi_resource_metrics(metrics, self.redactor.as_ref(), &self.limits));
+        async move {
+            let Some(metrics) = metrics else {
+                return Err(OTelSdkError::AlreadyShutdown);
+            };
+            let data_points = data_point_count(&metrics);
+            if data_points == 0 {
+                return Ok(());
+            }
+            match AssertSend::new(export_metrics(metrics)).await {
+                Ok(()) => {
+                    self.tracker.record_success();
+                    Ok(())
+                }
+                Err(e) => {
+                    self.tracker.record_failure(&e);
+                    record_dropped_metric_points(data_points);
+                    otel_error!(name: "export_metrics_internal_error", msg = format!("Operation failed due to an internal error: {}", e));
+                    Err(OTelSdkError::InternalFailure(e))
+                }
+            }
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+
+    fn shutdown_with_timeout(&self, _timeout: std::time::Duration) -> OTelSdkResult {
+        if self.is_shutdown.swap(true, Ordering::Relaxed) {
+            return Err(OTelSdkError::AlreadyShutdown);
+        }
+        Ok(())
+    }
+
+    fn temporality(&self) -> Temporality {
+        self.temporality
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        health::{dropped_metric_points, instance_exports},
+        recording_host::{set_export_error, take_metrics},
+        WasiMetricExporter,
+    };
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry_sdk::metrics::{reader::MetricReader, SdkMeterProvider};
+    use std::{
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    fn export(exporter: &WasiAsyncMetricExporter, metrics: &ResourceMetrics) -> OTelSdkResult {
+        let mut cx = Context::from_waker(Waker::noop());
+        match pin!(exporter.export(metrics)).poll(&mut cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => panic!("export should complete without waiting"),
+        }
+    }
+
+    #[test]
+    fn exports_metrics_and_tracks_health() {
+        let builder = WasiAsyncMetricExporter::builder().with_temporality(Temporality::Delta);
+        let health = builder.health_handle();
+        let exporter = builder.build();
+        assert_eq!(exporter.temporality(), Temporality::Delta);
+
+        let reader = WasiMetricExporter::builder()
+            .with_manual_export_only()
+            .build();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .build();
+        provider
+            .meter("test")
+            .u64_counter("requests")
+            .build()
+            .add(1, &[]);
+        let mut metrics = ResourceMetrics::default();
+        reader.collect(&mut metrics).unwrap();
+
+        let (succeeded, failed) = instance_exports(Signal::Metrics);
+        let dropped = dropped_metric_points();
+        export(&exporter, &metrics).unwrap();
+        let exports = take_metrics();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0].scope_metrics[0].metrics[0].name, "requests");
+
+        set_export_error(Some("host is full"));
+        assert!(export(&exporter, &metrics).is_err());
+        set_export_error(None);
+        // Other tests export concurrently, so only check that these exports were counted.
+        let (now_succeeded, now_failed) = instance_exports(Signal::Metrics);
+        assert!(now_succeeded > succeeded);
+        assert!(now_failed > failed);
+        assert!(dropped_metric_points() > dropped);
+        assert_eq!(health.health().successful_exports(), 1);
+        assert_eq!(health.health().failed_exports(), 1);
+        assert_eq!(exporter.health().last_error(), Some("host is full"));
+
+        exporter.shutdown().unwrap();
+        assert!(matches!(
+            export(&exporter, &metrics),
+            Err(OTelSdkError::AlreadyShutdown)
+        ));
+        assert!(take_metrics().is_empty());
+    }
+
+    #[test]
+    fn skip_collections_without_data_points() {
+        let exporter = WasiAsyncMetricExporter::new();
+
+        export(&exporter, &ResourceMetrics::default()).unwrap();
+
+        assert!(take_metrics().is_empty());
+        assert_eq!(exporter.health(), ExportHealth::default());
+    }
+}
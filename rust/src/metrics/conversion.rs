@@ -1,31 +1,82 @@
-use crate::wit::wasi::otel::metrics::*;
+use crate::{wit::wasi::otel::metrics::*, PayloadLimits, Redactor};
+use opentelemetry::trace::{SpanId, TraceId};
 
-impl From<opentelemetry_sdk::metrics::data::ResourceMetrics> for ResourceMetrics {
-    fn from(value: opentelemetry_sdk::metrics::data::ResourceMetrics) -> Self {
-        Self {
-            resource: value.resource().into(),
-            scope_metrics: value.scope_metrics().map(Into::into).collect(),
-        }
+pub fn to_wasi_resource_metrics(
+    value: &opentelemetry_sdk::metrics::data::ResourceMetrics,
+    redactor: Option<&Redactor>,
+    limits: &PayloadLimits,
+) -> ResourceMetrics {
+    ResourceMetrics {
+        resource: value.resource().into(),
+        scope_metrics: value
+            .scope_metrics()
+            .map(|sm| to_wasi_scope_metrics(sm, redactor, limits))
+            .collect(),
     }
 }
 
-impl From<&opentelemetry_sdk::metrics::data::ScopeMetrics> for ScopeMetrics {
-    fn from(value: &opentelemetry_sdk::metrics::data::ScopeMetrics) -> Self {
-        Self {
-            scope: value.scope().into(),
-            metrics: value.metrics().map(Into::into).collect(),
-        }
+fn to_wasi_scope_metrics(
+    value: &opentelemetry_sdk::metrics::data::ScopeMetrics,
+    redactor: Option<&Redactor>,
+    limits: &PayloadLimits,
+) -> ScopeMetrics {
+    ScopeMetrics {
+        scope: value.scope().into(),
+        metrics: value
+            .metrics()
+            .map(|m| to_wasi_metric(m, redactor, limits))
+            .collect(),
     }
 }
 
-impl From<&opentelemetry_sdk::metrics::data::Metric> for Metric {
-    fn from(value: &opentelemetry_sdk::metrics::data::Metric) -> Self {
-        Self {
-            name: value.name().to_string(),
-            description: value.description().to_string(),
-            unit: value.unit().to_string(),
-            data: value.data().into(),
-        }
+fn to_wasi_metric(
+    value: &opentelemetry_sdk::metrics::data::Metric,
+    redactor: Option<&Redactor>,
+    limits: &PayloadLimits,
+) -> Metric {
+    Metric {
+        name: value.name().to_string(),
+        description: value.description().to_string(),
+        unit: value.unit().to_string(),
+        data: to_wasi_metric_data(value.data(), redactor, limits),
+    }
+}
+
+/// Converts data point attributes, applying the redactor.
+///
+/// Limits aren't applied, as dropping or truncating data point attributes could merge distinct
+/// series into one.
+pub(super) fn to_wasi_attributes<'a>(
+    attributes: impl Iterator<Item = &'a opentelemetry::KeyValue>,
+    redactor: Option<&Redactor>,
+) -> Vec<KeyValue> {
+    redact_attributes(attributes, redactor)
+        .iter()
+        .map(Into::into)
+        .collect()
+}
+
+/// Converts the filtered attributes of an exemplar, applying the redactor and limits.
+pub(super) fn to_wasi_filtered_attributes<'a>(
+    attributes: impl Iterator<Item = &'a opentelemetry::KeyValue>,
+    redactor: Option<&Redactor>,
+    limits: &PayloadLimits,
+) -> Vec<KeyValue> {
+    let mut attributes = redact_attributes(attributes, redactor);
+    limits.limit_key_values(&mut attributes);
+    attributes.iter().map(Into::into).collect()
+}
+
+/// Applies the redactor to attributes.
+pub(super) fn redact_attributes<'a>(
+    attributes: impl Iterator<Item = &'a opentelemetry::KeyValue>,
+    redactor: Option<&Redactor>,
+) -> Vec<opentelemetry::KeyValue> {
+    match redactor {
+        Some(redactor) => attributes
+            .filter_map(|kv| redactor.redact_key_value(kv))
+            .collect(),
+        None => attributes.cloned().collect(),
     }
 }
 
@@ -37,6 +88,8 @@ macro_rules! metric_data_to_wasi {
         $wasi_sum_type:ident,
         $wasi_histogram_type:ident,
         $wasi_exponential_histogram_type:ident,
+        $redactor:expr,
+        $limits:expr,
     ) => {
         match $otel_metric_data {
             opentelemetry_sdk::metrics::data::MetricData::Gauge(g) => {
@@ -45,9 +98,12 @@ macro_rules! metric_data_to_wasi {
                         data_points: g
                             .data_points()
                             .map(|dp| crate::wit::wasi::otel::metrics::GaugeDataPoint {
-                                attributes: dp.attributes().into_iter().map(Into::into).collect(),
+                                attributes: to_wasi_attributes(dp.attributes(), $redactor),
                                 value: dp.value().into(),
-                                exemplars: dp.exemplars().into_iter().map(Into::into).collect(),
+                                exemplars: dp
+                                    .exemplars()
+                                    .map(|e| to_wasi_exemplar(e, $redactor, $limits))
+                                    .collect(),
                             })
                             .collect(),
                         start_time: g.start_time().map(Into::into),
@@ -61,9 +117,12 @@ macro_rules! metric_data_to_wasi {
                         data_points: s
                             .data_points()
                             .map(|dp| crate::wit::wasi::otel::metrics::SumDataPoint {
-                                attributes: dp.attributes().into_iter().map(Into::into).collect(),
+                                attributes: to_wasi_attributes(dp.attributes(), $redactor),
                                 value: dp.value().into(),
-                                exemplars: dp.exemplars().into_iter().map(Into::into).collect(),
+                                exemplars: dp
+                                    .exemplars()
+                                    .map(|e| to_wasi_exemplar(e, $redactor, $limits))
+                                    .collect(),
                             })
                             .collect(),
                         start_time: s.start_time().into(),
@@ -79,14 +138,17 @@ macro_rules! metric_data_to_wasi {
                         data_points: h
                             .data_points()
                             .map(|dp| crate::wit::wasi::otel::metrics::HistogramDataPoint {
-                                attributes: dp.attributes().into_iter().map(Into::into).collect(),
+                                attributes: to_wasi_attributes(dp.attributes(), $redactor),
                                 count: dp.count(),
                                 bounds: dp.bounds().collect(),
                                 bucket_counts: dp.bucket_counts().collect(),
                                 min: dp.min().map(Into::into),
                                 max: dp.max().map(Into::into),
                                 sum: dp.sum().into(),
-                                exemplars: dp.exemplars().into_iter().map(Into::into).collect(),
+                                exemplars: dp
+                                    .exemplars()
+                                    .map(|e| to_wasi_exemplar(e, $redactor, $limits))
+                                    .collect(),
                             })
                             .collect(),
                         start_time: h.start_time().into(),
@@ -102,11 +164,7 @@ macro_rules! metric_data_to_wasi {
                             .data_points()
                             .map(|dp| {
                                 crate::wit::wasi::otel::metrics::ExponentialHistogramDataPoint {
-                                    attributes: dp
-                                        .attributes()
-                                        .into_iter()
-                                        .map(Into::into)
-                                        .collect(),
+                                    attributes: to_wasi_attributes(dp.attributes(), $redactor),
                                     count: dp.count() as u64,
                                     min: dp.min().map(Into::into),
                                     max: dp.max().map(Into::into),
@@ -116,7 +174,10 @@ macro_rules! metric_data_to_wasi {
                                     positive_bucket: dp.positive_bucket().into(),
                                     negative_bucket: dp.negative_bucket().into(),
                                     zero_threshold: dp.zero_threshold(),
-                                    exemplars: dp.exemplars().map(Into::into).collect(),
+                                    exemplars: dp
+                                        .exemplars()
+                                        .map(|e| to_wasi_exemplar(e, $redactor, $limits))
+                                        .collect(),
                                 }
                             })
                             .collect(),
@@ -130,32 +191,40 @@ macro_rules! metric_data_to_wasi {
     };
 }
 
-impl From<&opentelemetry_sdk::metrics::data::AggregatedMetrics> for MetricData {
-    fn from(value: &opentelemetry_sdk::metrics::data::AggregatedMetrics) -> Self {
-        use opentelemetry_sdk::metrics::data as sdk;
-        match value {
-            sdk::AggregatedMetrics::F64(data) => metric_data_to_wasi!(
-                data,
-                F64Gauge,
-                F64Sum,
-                F64Histogram,
-                F64ExponentialHistogram,
-            ),
-            sdk::AggregatedMetrics::U64(data) => metric_data_to_wasi!(
-                data,
-                U64Gauge,
-                U64Sum,
-                U64Histogram,
-                U64ExponentialHistogram,
-            ),
-            sdk::AggregatedMetrics::I64(data) => metric_data_to_wasi!(
-                data,
-                S64Gauge,
-                S64Sum,
-                S64Histogram,
-                S64ExponentialHistogram,
-            ),
-        }
+fn to_wasi_metric_data(
+    value: &opentelemetry_sdk::metrics::data::AggregatedMetrics,
+    redactor: Option<&Redactor>,
+    limits: &PayloadLimits,
+) -> MetricData {
+    use opentelemetry_sdk::metrics::data as sdk;
+    match value {
+        sdk::AggregatedMetrics::F64(data) => metric_data_to_wasi!(
+            data,
+            F64Gauge,
+            F64Sum,
+            F64Histogram,
+            F64ExponentialHistogram,
+            redactor,
+            limits,
+        ),
+        sdk::AggregatedMetrics::U64(data) => metric_data_to_wasi!(
+            data,
+            U64Gauge,
+            U64Sum,
+            U64Histogram,
+            U64ExponentialHistogram,
+            redactor,
+            limits,
+        ),
+        sdk::AggregatedMetrics::I64(data) => metric_data_to_wasi!(
+            data,
+            S64Gauge,
+            S64Sum,
+            S64Histogram,
+            S64ExponentialHistogram,
+            redactor,
+            limits,
+        ),
     }
 }
 
@@ -178,39 +247,30 @@ impl From<opentelemetry_sdk::metrics::Temporality> for Temporality {
     }
 }
 
-impl From<&opentelemetry_sdk::metrics::data::Exemplar<f64>> for Exemplar {
-    fn from(value: &opentelemetry_sdk::metrics::data::Exemplar<f64>) -> Self {
-        Self {
-            filtered_attributes: value.filtered_attributes().map(Into::into).collect(),
-            time: value.time().into(),
-            value: value.value.into(),
-            span_id: String::from_utf8(value.span_id().to_vec()).unwrap(),
-            trace_id: String::from_utf8(value.trace_id().to_vec()).unwrap(),
-        }
+fn to_wasi_exemplar<T: Into<MetricNumber> + Copy>(
+    value: &opentelemetry_sdk::metrics::data::Exemplar<T>,
+    redactor: Option<&Redactor>,
+    limits: &PayloadLimits,
+) -> Exemplar {
+    Exemplar {
+        filtered_attributes: to_wasi_filtered_attributes(
+            value.filtered_attributes(),
+            redactor,
+            limits,
+        ),
+        time: value.time().into(),
+        value: value.value.into(),
+        span_id: to_wasi_id(SpanId::from_bytes(*value.span_id()), SpanId::INVALID),
+        trace_id: to_wasi_id(TraceId::from_bytes(*value.trace_id()), TraceId::INVALID),
     }
 }
 
-impl From<&opentelemetry_sdk::metrics::data::Exemplar<u64>> for Exemplar {
-    fn from(value: &opentelemetry_sdk::metrics::data::Exemplar<u64>) -> Self {
-        Self {
-            filtered_attributes: value.filtered_attributes().map(Into::into).collect(),
-            time: value.time().into(),
-            value: value.value.into(),
-            span_id: String::from_utf8(value.span_id().to_vec()).unwrap(),
-            trace_id: String::from_utf8(value.trace_id().to_vec()).unwrap(),
-        }
-    }
-}
-
-impl From<&opentelemetry_sdk::metrics::data::Exemplar<i64>> for Exemplar {
-    fn from(value: &opentelemetry_sdk::metrics::data::Exemplar<i64>) -> Self {
-        Self {
-            filtered_attributes: value.filtered_attributes().map(Into::into).collect(),
-            time: value.time().into(),
-            value: value.value.into(),
-            span_id: String::from_utf8(value.span_id().to_vec()).unwrap(),
-            trace_id: String::from_utf8(value.trace_id().to_vec()).unwrap(),
-        }
+/// Formats a trace or span ID as lowercase hex, or as an empty string if no span was active.
+pub(super) fn to_wasi_id<T: PartialEq + std::fmt::Display>(id: T, invalid: T) -> String {
+    if id == invalid {
+        String::new()
+    } else {
+        id.to_string()
     }
 }
 
@@ -0,0 +1,540 @@
+use crate::{
+    metrics::{
+        conversion::{
+            redact_attributes, to_wasi_attributes, to_wasi_filtered_attributes, to_wasi_id,
+        },
+        retry::DataPoint,
+    },
+    wit::wasi::otel::metrics::{Exemplar, MetricData, MetricNumber, ResourceMetrics},
+    PayloadLimits, Redactor, WasiMetricExporter, WasiMetricView,
+};
+use opentelemetry::{
+    metrics::{
+        AsyncInstrumentBuilder, Counter, Gauge, Histogram, HistogramBuilder, InstrumentBuilder,
+        InstrumentProvider, Meter, MeterProvider, ObservableCounter, ObservableGauge,
+        ObservableUpDownCounter, SyncInstrument, UpDownCounter,
+    },
+    trace::{SpanContext, SpanId, TraceContextExt, TraceId},
+    Context, InstrumentationScope, KeyValue,
+};
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+/// Decides which measurements are sampled as exemplars.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExemplarFilter {
+    /// Sample every measurement.
+    AlwaysOn,
+    /// Sample measurements recorded while a sampled span is active, including a span context
+    /// extracted from the host with `TraceContextPropagator`.
+    #[default]
+    TraceBased,
+    /// Never sample measurements.
+    AlwaysOff,
+}
+
+/// A meter provider whose synchronous instruments sample measurements as exemplars.
+///
+/// The OpenTelemetry SDK doesn't collect exemplars itself, so instruments created by this provider
+/// offer their measurements to the [`WasiMetricExporter`] it was created with. The exporter's
+/// exemplar filter decides which measurements are sampled, and they are attached to the data
+/// points they were aggregated into on the next export, along with the trace and span IDs of the
+/// span that was active when they were recorded.
+///
+/// Exemplars are matched to data points by instrument name, so they are not attached to metrics
+/// renamed by a view.
+///
+/// # Example
+/// ```ignore
+/// let exporter = WasiMetricExporter::builder()
+///     .with_exemplar_filter(ExemplarFilter::TraceBased)
+///     .build();
+/// let provider = WasiMeterProvider::new(exporter.meter_provider_builder().build(), &exporter);
+/// global::set_meter_provider(provider);
+/// ```
+#[derive(Debug, Clone)]
+pub struct WasiMeterProvider {
+    provider: SdkMeterProvider,
+    reservoir: Arc<ExemplarReservoir>,
+}
+
+impl WasiMeterProvider {
+    /// Wrap `provider`, which `exporter` must be a reader of, so its instruments sample exemplars
+    /// for `exporter`.
+    pub fn new(provider: SdkMeterProvider, exporter: &WasiMetricExporter) -> Self {
+        Self {
+            provider,
+            reservoir: exporter.exemplar_reservoir(),
+        }
+    }
+
+    /// The wrapped SDK meter provider, e.g. to flush or shut it down.
+    pub fn sdk_provider(&self) -> &SdkMeterProvider {
+        &self.provider
+    }
+}
+
+impl MeterProvider for WasiMeterProvider {
+    fn meter_with_scope(&self, scope: InstrumentationScope) -> Meter {
+        let scope_name = scope.name().to_string();
+        Meter::new(Arc::new(SampledInstruments {
+            meter: self.provider.meter_with_scope(scope),
+            scope: scope_name,
+            reservoir: self.reservoir.clone(),
+        }))
+    }
+}
+
+/// Measurements sampled as exemplars, waiting to be attached to data points on the next export.
+#[derive(Debug)]
+pub(crate) struct ExemplarReservoir {
+    filter: ExemplarFilter,
+    size: usize,
+    views: Vec<WasiMetricView>,
+    samples: Mutex<HashMap<SeriesId, VecDeque<Sample>>>,
+}
+
+/// The scope, instrument and attributes a measurement was recorded with.
+type SeriesId = (String, String, Vec<(String, String)>);
+
+#[derive(Debug)]
+struct Sample {
+    attributes: Vec<KeyValue>,
+    value: MetricNumber,
+    time: SystemTime,
+    span_context: SpanContext,
+}
+
+impl ExemplarReservoir {
+    /// Creates a reservoir for the instruments of a provider with `views` registered.
+    pub(crate) fn new(filter: ExemplarFilter, size: usize, views: Vec<WasiMetricView>) -> Self {
+        Self {
+            filter,
+            size,
+            views,
+            samples: Mutex::default(),
+        }
+    }
+
+    /// Samples a measurement if it passes the filter, keeping the most recent measurements of
+    /// each series.
+    fn offer(&self, scope: &str, instrument: &str, value: MetricNumber, attributes: &[KeyValue]) {
+        if self.size == 0 || self.filter == ExemplarFilter::AlwaysOff {
+            return;
+        }
+        let span_context = Context::map_current(|cx| cx.span().span_context().clone());
+        if self.filter == ExemplarFilter::TraceBased && !span_context.is_sampled() {
+            return;
+        }
+        let mut series: Vec<_> = attributes
+            .iter()
+            .map(|kv| (kv.key.to_string(), kv.value.to_string()))
+            .collect();
+        series.sort();
+        let mut samples = self.samples.lock().unwrap();
+        let samples = samples
+            .entry((scope.to_string(), instrument.to_string(), series))
+            .or_default();
+        if samples.len() == self.size {
+            samples.pop_front();
+        }
+        samples.push_back(Sample {
+            attributes: attributes.to_vec(),
+            value,
+            time: SystemTime::now(),
+            span_context,
+        });
+    }
+
+    /// Attaches the sampled measurements to the data points they were aggregated into and clears
+    /// them.
+    ///
+    /// A measurement belongs to the data point of the series it was recorded with. If a view
+    /// drops some of the instrument's attributes, it belongs to the data point whose attributes it
+    /// was recorded with, and the attributes the view dropped become the exemplar's filtered
+    /// attributes.
+    pub(crate) fn attach(
+        &self,
+        metrics: &mut ResourceMetrics,
+        redactor: Option<&Redactor>,
+        limits: &PayloadLimits,
+    ) {
+        let samples = std::mem::take(&mut *self.samples.lock().unwrap());
+        if samples.is_empty() {
+            return;
+        }
+        let mut by_instrument: HashMap<(String, String), Vec<Sample>> = HashMap::new();
+        for ((scope, instrument, _), series) in samples {
+            by_instrument
+                .entry((scope, instrument))
+                .or_default()
+                .extend(series);
+        }
+        for scope in &mut metrics.scope_metrics {
+            for metric in &mut scope.metrics {
+                let Some(samples) =
+                    by_instrument.get_mut(&(scope.scope.name.clone(), metric.name.clone()))
+                else {
+                    continue;
+                };
+                let filtered = self
+                    .views
+                    .iter()
+                    .any(|view| view.filters_attributes(&metric.name));
+                samples.sort_by_key(|sample| sample.time);
+                let samples: Vec<_> = samples
+                    .iter()
+                    .map(|s| {
+                        let redacted = redact_attributes(s.attributes.iter(), redactor);
+                        (to_wasi_attributes(redacted.iter(), None), redacted, s)
+                    })
+                    .collect();
+                for_each_point(&mut metric.data, |point| {
+                    let mut exemplars: Vec<_> = samples
+                        .iter()
+                        .filter(|(attributes, _, _)| {
+                            (filtered || attributes.len() == point.attributes().len())
+                                && point.attributes().iter().all(|kv| {
+                                    attributes
+                                        .iter()
+                                        .any(|a| a.key == kv.key && a.value == kv.value)
+                                })
+                        })
+                        .map(|(_, redacted, sample)| Exemplar {
+                            filtered_attributes: to_wasi_filtered_attributes(
+                                redacted.iter().filter(|a| {
+                                    !point.attributes().iter().any(|kv| kv.key == a.key.as_str())
+                                }),
+                                None,
+                                limits,
+                            ),
+                            time: sample.time.into(),
+                            value: sample.value,
+                            span_id: to_wasi_id(sample.span_context.span_id(), SpanId::INVALID),
+                            trace_id: to_wasi_id(sample.span_context.trace_id(), TraceId::INVALID),
+                        })
+                        .collect();
+                    // Keep the most recent exemplars when several series were aggregated into
+                    // the same data point.
+                    exemplars.drain(..exemplars.len().saturating_sub(self.size));
+                    point.exemplars_mut().extend(exemplars);
+                });
+            }
+        }
+    }
+}
+
+fn for_each_point(data: &mut MetricData, mut f: impl FnMut(&mut dyn DataPoint)) {
+    use MetricData::*;
+    match data {
+        F64Gauge(g) | U64Gauge(g) | S64Gauge(g) => g.data_points.iter_mut().for_each(|p| f(p)),
+        F64Sum(s) | U64Sum(s) | S64Sum(s) => s.data_points.iter_mut().for_each(|p| f(p)),
+        F64Histogram(h) | U64Histogram(h) | S64Histogram(h) => {
+            h.data_points.iter_mut().for_each(|p| f(p))
+        }
+        F64ExponentialHistogram(h) | U64ExponentialHistogram(h) | S64ExponentialHistogram(h) => {
+            h.data_points.iter_mut().for_each(|p| f(p))
+        }
+    }
+}
+
+/// Creates instruments with the SDK meter that also offer their measurements to the reservoir.
+struct SampledInstruments {
+    meter: Meter,
+    scope: String,
+    reservoir: Arc<ExemplarReservoir>,
+}
+
+impl SampledInstruments {
+    fn sampled<T: Into<MetricNumber> + Copy + 'static>(
+        &self,
+        name: Cow<'static, str>,
+        record: impl Fn(T, &[KeyValue]) + Send + Sync + 'static,
+    ) -> Arc<SampledInstrument<T>> {
+        Arc::new(SampledInstrument {
+            record: Box::new(record),
+            scope: self.scope.clone(),
+            name: name.into_owned(),
+            reservoir: self.reservoir.clone(),
+        })
+    }
+}
+
+/// Records a measurement with an SDK instrument.
+type Record<T> = Box<dyn Fn(T, &[KeyValue]) + Send + Sync>;
+
+/// A synchronous instrument that records with the SDK before sampling the measurement.
+struct SampledInstrument<T> {
+    record: Record<T>,
+    scope: String,
+    name: String,
+    reservoir: Arc<ExemplarReservoir>,
+}
+
+impl<T: Into<MetricNumber> + Copy> SyncInstrument<T> for SampledInstrument<T> {
+    fn measure(&self, measurement: T, attributes: &[KeyValue]) {
+        (self.record)(measurement, attributes);
+        self.reservoir
+            .offer(&self.scope, &self.name, measurement.into(), attributes);
+    }
+}
+
+/// Copies the description and unit of an instrument builder onto one for the SDK meter.
+macro_rules! configure {
+    ($sdk_builder:expr, $builder:expr) => {{
+        let mut sdk_builder = $sdk_builder;
+        if let Some(description) = $builder.description.clone() {
+            sdk_builder = sdk_builder.with_description(description);
+        }
+        if let Some(unit) = $builder.unit.clone() {
+            sdk_builder = sdk_builder.with_unit(unit);
+        }
+        sdk_builder
+    }};
+}
+
+macro_rules! sampled_instrument {
+    ($method:ident, $instrument:ident<$t:ty>, $record:ident) => {
+        fn $method(&self, builder: InstrumentBuilder<'_, $instrument<$t>>) -> $instrument<$t> {
+            let instrument = configure!(self.meter.$method(builder.name.clone()), builder).build();
+            $instrument::new(self.sampled(builder.name, move |value, attributes| {
+                instrument.$record(value, attributes)
+            }))
+        }
+    };
+}
+
+macro_rules! sampled_histogram {
+    ($method:ident, $t:ty) => {
+        fn $method(&self, builder: HistogramBuilder<'_, Histogram<$t>>) -> Histogram<$t> {
+            let mut sdk_builder = configure!(self.meter.$method(builder.name.clone()), builder);
+            if let Some(boundaries) = builder.boundaries.clone() {
+                sdk_builder = sdk_builder.with_boundaries(boundaries);
+            }
+            let instrument = sdk_builder.build();
+            Histogram::new(self.sampled(builder.name, move |value, attributes| {
+                instrument.record(value, attributes)
+            }))
+        }
+    };
+}
+
+// Observable instruments are only created with the SDK meter, as exemplars aren't sampled from
+// asynchronous measurements.
+macro_rules! observable_instrument {
+    ($method:ident, $instrument:ident<$t:ty>) => {
+        fn $method(
+            &self,
+            builder: AsyncInstrumentBuilder<'_, $instrument<$t>, $t>,
+        ) -> $instrument<$t> {
+            let mut sdk_builder = configure!(self.meter.$method(builder.name.clone()), builder);
+            for callback in builder.callbacks {
+                sdk_builder = sdk_builder.with_callback(callback);
+            }
+            sdk_builder.build()
+        }
+    };
+}
+
+impl InstrumentProvider for SampledInstruments {
+    sampled_instrument!(u64_counter, Counter<u64>, add);
+    sampled_instrument!(f64_counter, Counter<f64>, add);
+    sampled_instrument!(i64_up_down_counter, UpDownCounter<i64>, add);
+    sampled_instrument!(f64_up_down_counter, UpDownCounter<f64>, add);
+    sampled_instrument!(u64_gauge, Gauge<u64>, record);
+    sampled_instrument!(f64_gauge, Gauge<f64>, record);
+    sampled_instrument!(i64_gauge, Gauge<i64>, record);
+    sampled_histogram!(f64_histogram, f64);
+    sampled_histogram!(u64_histogram, u64);
+    observable_instrument!(u64_observable_counter, ObservableCounter<u64>);
+    observable_instrument!(f64_observable_counter, ObservableCounter<f64>);
+    observable_instrument!(i64_observable_up_down_counter, ObservableUpDownCounter<i64>);
+    observable_instrument!(f64_observable_up_down_counter, ObservableUpDownCounter<f64>);
+    observable_instrument!(u64_observable_gauge, ObservableGauge<u64>);
+    observable_instrument!(i64_observable_gauge, ObservableGauge<i64>);
+    observable_instrument!(f64_observable_gauge, ObservableGauge<f64>);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{recording_host::take_metrics, wit::wasi::otel::metrics::MetricData};
+    use opentelemetry::trace::{SpanId, TraceFlags, TraceId, TraceState};
+
+    #[test]
+    fn exemplars_carry_the_current_span_context() {
+        let exporter = WasiMetricExporter::builder()
+            .with_exemplar_filter(ExemplarFilter::TraceBased)
+            .build();
+        let provider = WasiMeterProvider::new(exporter.meter_provider_builder().build(), &exporter);
+        let counter = provider.meter("test").u64_counter("requests").build();
+
+        counter.add(1, &[KeyValue::new("route", "/untraced")]);
+        let span_context = SpanContext::new(
+            TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        );
+        {
+            let _guard = Context::current()
+                .with_remote_span_context(span_context)
+                .attach();
+            counter.add(
+                2,
+                &[
+                    KeyValue::new("route", "/traced"),
+                    KeyValue::new("user", "alice"),
+                ],
+            );
+        }
+        exporter.export().unwrap();
+
+        let exports = take_metrics();
+        let MetricData::U64Sum(sum) = &exports[0].scope_metrics[0].metrics[0].data else {
+            panic!("expected a u64 sum");
+        };
+        let exemplars: Vec<_> = sum.data_points.iter().flat_map(|p| &p.exemplars).collect();
+        assert_eq!(exemplars.len(), 1);
+        assert_eq!(exemplars[0].trace_id, "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(exemplars[0].span_id, "00f067aa0ba902b7");
+        assert!(matches!(exemplars[0].value, MetricNumber::U64(2)));
+        assert!(exemplars[0].filtered_attributes.is_empty());
+
+        // Exemplars are only reported once.
+        exporter.export().unwrap();
+        let exports = take_metrics();
+        let MetricData::U64Sum(sum) = &exports[0].scope_metrics[0].metrics[0].data else {
+            panic!("expected a u64 sum");
+        };
+        assert!(sum.data_points.iter().all(|p| p.exemplars.is_empty()));
+    }
+
+    fn export_exemplars(
+        exporter: WasiMetricExporter,
+        record: impl FnOnce(&Counter<u64>),
+    ) -> Vec<crate::wit::wasi::otel::metrics::SumDataPoint> {
+        let provider = WasiMeterProvider::new(exporter.meter_provider_builder().build(), &exporter);
+        record(&provider.meter("test").u64_counter("requests").build());
+        exporter.export().unwrap();
+        let mut exports = take_metrics();
+        let MetricData::U64Sum(sum) = exports[0].scope_metrics[0].metrics.remove(0).data else {
+            panic!("expected a u64 sum");
+        };
+        sum.data_points
+    }
+
+    #[test]
+    fn always_on_samples_measurements_without_a_span() {
+        let exporter = WasiMetricExporter::builder()
+            .with_exemplar_filter(ExemplarFilter::AlwaysOn)
+            .build();
+        let points = export_exemplars(exporter, |counter| counter.add(3, &[]));
+
+        assert_eq!(points[0].exemplars.len(), 1);
+        assert!(matches!(points[0].exemplars[0].value, MetricNumber::U64(3)));
+        assert_eq!(points[0].exemplars[0].trace_id, "");
+        assert_eq!(points[0].exemplars[0].span_id, "");
+    }
+
+    #[test]
+    fn always_off_samples_nothing() {
+        let exporter = WasiMetricExporter::builder()
+            .with_exemplar_filter(ExemplarFilter::AlwaysOff)
+            .build();
+        let points = export_exemplars(exporter, |counter| counter.add(3, &[]));
+
+        assert!(points[0].exemplars.is_empty());
+    }
+
+    #[cfg(feature = "otlp")]
+    #[test]
+    fn sample_nothing_for_otlp_encoded_metrics() {
+        let exporter = WasiMetricExporter::builder()
+            .with_exemplar_filter(ExemplarFilter::AlwaysOn)
+            .with_otlp_encoding()
+            .with_manual_export_only()
+            .build();
+        let provider = WasiMeterProvider::new(exporter.meter_provider_builder().build(), &exporter);
+        let counter = provider.meter("test").u64_counter("requests").build();
+        (1..=3).for_each(|i| counter.add(1, &[KeyValue::new("user", i)]));
+
+        assert!(exporter
+            .exemplar_reservoir()
+            .samples
+            .lock()
+            .unwrap()
+            .is_empty());
+        exporter.export().unwrap();
+        assert_eq!(crate::recording_host::take_otlp_metrics().len(), 1);
+    }
+
+    #[test]
+    fn keep_the_most_recent_measurements_of_each_series() {
+        let exporter = WasiMetricExporter::builder()
+            .with_exemplar_filter(ExemplarFilter::AlwaysOn)
+            .with_exemplar_reservoir_size(2)
+            .build();
+        let points = export_exemplars(exporter, |counter| {
+            (1..=3).for_each(|value| counter.add(value, &[]))
+        });
+
+        let values: Vec<_> = points[0].exemplars.iter().map(|e| e.value).collect();
+        assert!(matches!(
+            values[..],
+            [MetricNumber::U64(2), MetricNumber::U64(3)]
+        ));
+    }
+
+    #[test]
+    fn attach_exemplars_to_their_own_series() {
+        let exporter = WasiMetricExporter::builder()
+            .with_exemplar_filter(ExemplarFilter::AlwaysOn)
+            .build();
+        let points = export_exemplars(exporter, |counter| {
+            counter.add(1, &[KeyValue::new("route", "/a")]);
+            counter.add(
+                2,
+                &[KeyValue::new("route", "/a"), KeyValue::new("user", "alice")],
+            );
+        });
+
+        assert_eq!(points.len(), 2);
+        for point in &points {
+            assert_eq!(point.exemplars.len(), 1);
+            assert!(point.exemplars[0].filtered_attributes.is_empty());
+        }
+    }
+
+    #[test]
+    fn attach_exemplars_to_series_merged_by_a_view() {
+        let exporter = WasiMetricExporter::builder()
+            .with_exemplar_filter(ExemplarFilter::AlwaysOn)
+            .with_exemplar_reservoir_size(2)
+            .with_view(
+                WasiMetricView::builder("requests")
+                    .with_allowed_attribute_keys(["route"])
+                    .build(),
+            )
+            .build();
+        let points = export_exemplars(exporter, |counter| {
+            counter.add(1, &[KeyValue::new("route", "/a")]);
+            counter.add(
+                2,
+                &[KeyValue::new("route", "/a"), KeyValue::new("user", "alice")],
+            );
+        });
+
+        assert_eq!(points.len(), 1);
+        let exemplars = &points[0].exemplars;
+        assert_eq!(exemplars.len(), 2);
+        assert!(exemplars[0].filtered_attributes.is_empty());
+        assert_eq!(exemplars[1].filtered_attributes[0].key, "user");
+    }
+}
@@ -1,12 +1,37 @@
-use crate::wit::wasi;
-use opentelemetry::otel_error;
+#[cfg(all(test, feature = "otlp"))]
+use crate::recording_host::export_otlp_metrics;
+#[cfg(test)]
+use crate::recording_host::{export_metrics as host_export, now};
+#[cfg(all(not(test), feature = "otlp"))]
+use crate::wit::otlp::wasi::otel::otlp::export_metrics as export_otlp_metrics;
+#[cfg(not(test))]
+use crate::wit::wasi::{clocks::monotonic_clock::now, otel::metrics::export as host_export};
+use crate::{
+    health::{record_dropped_metric_points, record_instance_export, Signal},
+    metrics::{
+        conversion::to_wasi_resource_metrics,
+        exemplar::ExemplarReservoir,
+        retry::{data_point_count, RetryBuffer},
+        state::CumulativeState,
+        unchanged::UnchangedFilter,
+    },
+    ExemplarFilter, MetricStateStore, PayloadLimits, Redactor, RetryBufferLimits, WasiMetricView,
+};
+use opentelemetry::{otel_error, otel_warn};
 use opentelemetry_sdk::{
     error::{OTelSdkError, OTelSdkResult},
     metrics::{
-        data::ResourceMetrics, reader::MetricReader, InstrumentKind, ManualReader, Temporality,
+        data::ResourceMetrics, reader::MetricReader, Instrument, InstrumentKind, ManualReader,
+        MeterProviderBuilder, SdkMeterProvider, Temporality,
+    },
+};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Weak,
     },
+    time::Duration,
 };
-use std::sync::Arc;
 
 /// A metric exporter that sends OpenTelemetry metrics to a WASI host.
 ///
@@ -31,20 +56,72 @@ use std::sync::Arc;
 /// // Measure something...
 /// exporter.export()?; // User must manually trigger export to host at some point before the end of the code.
 /// ```
+///
+/// Clones share the same reader, so the final export on drop happens once, when the last clone
+/// goes away. Calling `force_flush` or `shutdown` on the `SdkMeterProvider` also exports to the
+/// host, and shutting it down performs the final export instead.
 #[derive(Debug, Clone)]
 pub struct WasiMetricExporter {
-    reader: Arc<ManualReader>,
+    inner: Arc<ExporterInner>,
+}
+
+#[derive(Debug)]
+struct ExporterInner {
+    reader: ManualReader,
+    views: Vec<WasiMetricView>,
+    /// Set while views are configured but [`WasiMetricExporter::meter_provider_builder`] has not
+    /// registered them with a provider.
+    views_unregistered: AtomicBool,
+    is_shutdown: AtomicBool,
     export_on_drop: bool,
+    host: HostExport,
+}
+
+/// Sends collected metrics to the host over `wasi:otel/metrics` or `wasi:otel/otlp`.
+#[derive(Debug)]
+pub(super) struct HostExport {
+    pub(super) redactor: Option<Redactor>,
+    pub(super) limits: PayloadLimits,
+    pub(super) retry: Option<RetryBuffer>,
+    pub(super) unchanged: Option<UnchangedFilter>,
+    pub(super) state: Option<CumulativeState>,
+    pub(super) exemplars: Arc<ExemplarReservoir>,
+    #[cfg(feature = "otlp")]
+    pub(super) otlp_encoding: bool,
 }
 
 pub struct WasiMetricExporterBuilder {
     export_on_drop: bool,
+    redactor: Option<Redactor>,
+    limits: PayloadLimits,
+    temporality: Temporality,
+    views: Vec<WasiMetricView>,
+    retry: Option<RetryBufferLimits>,
+    skip_unchanged: bool,
+    state: Option<(Box<dyn MetricStateStore>, String)>,
+    max_state_series: usize,
+    exemplar_filter: ExemplarFilter,
+    exemplar_reservoir_size: usize,
+    #[cfg(feature = "otlp")]
+    otlp_encoding: bool,
 }
 
 impl Default for WasiMetricExporterBuilder {
     fn default() -> Self {
         Self {
             export_on_drop: true,
+            redactor: None,
+            limits: PayloadLimits::default(),
+            temporality: Temporality::default(),
+            views: Vec::new(),
+            retry: None,
+            skip_unchanged: false,
+            state: None,
+            max_state_series: 4096,
+            exemplar_filter: ExemplarFilter::default(),
+            exemplar_reservoir_size: 1,
+            #[cfg(feature = "otlp")]
+            otlp_encoding: false,
         }
     }
 }
@@ -74,11 +151,167 @@ impl WasiMetricExporterBuilder {
         self
     }
 
+    /// Redact data point and exemplar attributes before they are sent to the host.
+    ///
+    /// Removing attributes with a deny or allow list can cause distinct time series to be reported
+    /// with identical attributes. Prefer dropping attributes with a view where possible.
+    #[cfg(feature = "redaction")]
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+
+    /// Truncate data point and exemplar attributes that exceed `limits`.
+    pub fn with_limits(mut self, limits: PayloadLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Set the temporality metrics are aggregated with. Defaults to cumulative.
+    pub fn with_temporality(mut self, temporality: Temporality) -> Self {
+        self.temporality = temporality;
+        self
+    }
+
+    /// Send metrics to the host as an encoded OTLP `ExportMetricsServiceRequest` through the
+    /// `wasi:otel/otlp` interface instead of as WIT records.
+    ///
+    /// The redactor and limits are still applied before encoding. The retry buffer, skipping of
+    /// unchanged series and the state store only work on WIT records, so `build` ignores them with
+    /// a warning, and no exemplars are sampled.
+    #[cfg(feature = "otlp")]
+    pub fn with_otlp_encoding(mut self) -> Self {
+        self.otlp_encoding = true;
+        self
+    }
+
+    /// Keep metrics the host rejects and send them again with the next export.
+    ///
+    /// Delta sums and histograms kept from failed exports are merged with the newer data of the
+    /// same series, while cumulative data and gauges are replaced by it. Data that doesn't fit
+    /// within `limits` is dropped and reported by [`WasiRuntimeMetrics`].
+    ///
+    /// [`WasiRuntimeMetrics`]: crate::WasiRuntimeMetrics
+    pub fn with_retry_buffer(mut self, limits: RetryBufferLimits) -> Self {
+        self.retry = Some(limits);
+        self
+    }
+
+    /// Leave cumulative series out of an export if they haven't changed since the last
+    /// successful export.
+    ///
+    /// The host is expected to keep reporting the last value it received for a series. Gauges
+    /// and delta series are always sent.
+    pub fn with_skip_unchanged_series(mut self) -> Self {
+        self.skip_unchanged = true;
+        self
+    }
+
+    /// Persist cumulative sums and histograms to `store` under `key`, so they keep counting
+    /// across short-lived component instances.
+    ///
+    /// Metrics are aggregated with delta temporality, overriding [`with_temporality`], and each
+    /// export adds them to the totals in the store before reporting the totals as cumulative
+    /// series. Updates to the store are not atomic, so instances exporting concurrently can lose
+    /// each other's updates.
+    ///
+    /// [`with_temporality`]: WasiMetricExporterBuilder::with_temporality
+    pub fn with_state_store(
+        mut self,
+        store: impl MetricStateStore + 'static,
+        key: impl Into<String>,
+    ) -> Self {
+        self.state = Some((Box::new(store), key.into()));
+        self
+    }
+
+    /// Set the maximum number of series kept in the state store. Defaults to 4096.
+    ///
+    /// When more series are recorded, those updated least recently are evicted and restart from
+    /// zero the next time they are recorded.
+    pub fn with_max_state_series(mut self, max: usize) -> Self {
+        self.max_state_series = max;
+        self
+    }
+
+    /// Set which measurements are sampled as exemplars. Defaults to trace-based.
+    ///
+    /// Exemplars are only sampled by instruments created with a [`WasiMeterProvider`].
+    ///
+    /// [`WasiMeterProvider`]: crate::WasiMeterProvider
+    pub fn with_exemplar_filter(mut self, filter: ExemplarFilter) -> Self {
+        self.exemplar_filter = filter;
+        self
+    }
+
+    /// Set the number of exemplars kept for each series between exports. Defaults to 1.
+    ///
+    /// The most recent measurements are kept.
+    pub fn with_exemplar_reservoir_size(mut self, size: usize) -> Self {
+        self.exemplar_reservoir_size = size;
+        self
+    }
+
+    /// Add a view that customizes the metrics of matching instruments.
+    ///
+    /// Views only take effect on a provider built with
+    /// [`WasiMetricExporter::meter_provider_builder`]. Otherwise, a warning is reported on the
+    /// first export.
+    pub fn with_view(mut self, view: WasiMetricView) -> Self {
+        self.views.push(view);
+        self
+    }
+
+    /// Add a set of views, such as a preset shared across components.
+    pub fn with_views(mut self, views: impl IntoIterator<Item = WasiMetricView>) -> Self {
+        self.views.extend(views);
+        self
+    }
+
     /// Build the exporter.
-    pub fn build(self) -> WasiMetricExporter {
+    #[cfg_attr(not(feature = "otlp"), allow(unused_mut))]
+    pub fn build(mut self) -> WasiMetricExporter {
+        #[cfg(feature = "otlp")]
+        if self.otlp_encoding {
+            warn_ignored_by_otlp(&[
+                ("with_retry_buffer", self.retry.take().is_some()),
+                (
+                    "with_skip_unchanged_series",
+                    std::mem::take(&mut self.skip_unchanged),
+                ),
+                ("with_state_store", self.state.take().is_some()),
+            ]);
+            self.exemplar_reservoir_size = 0;
+        }
         WasiMetricExporter {
-            reader: Arc::new(ManualReader::builder().build()),
-            export_on_drop: self.export_on_drop,
+            inner: Arc::new(ExporterInner {
+                reader: ManualReader::builder()
+                    .with_temporality(match self.state {
+                        Some(_) => Temporality::Delta,
+                        None => self.temporality,
+                    })
+                    .build(),
+                views_unregistered: AtomicBool::new(!self.views.is_empty()),
+                views: self.views.clone(),
+                is_shutdown: AtomicBool::new(false),
+                export_on_drop: self.export_on_drop,
+                host: HostExport {
+                    redactor: self.redactor,
+                    limits: self.limits,
+                    retry: self.retry.map(RetryBuffer::new),
+                    unchanged: self.skip_unchanged.then(UnchangedFilter::default),
+                    state: self.state.map(|(store, key)| {
+                        CumulativeState::new(store, key, self.max_state_series)
+                    }),
+                    exemplars: Arc::new(ExemplarReservoir::new(
+                        self.exemplar_filter,
+                        self.exemplar_reservoir_size,
+                        self.views,
+                    )),
+                    #[cfg(feature = "otlp")]
+                    otlp_encoding: self.otlp_encoding,
+                },
+            }),
         }
     }
 }
@@ -89,10 +322,10 @@ impl Default for WasiMetricExporter {
     }
 }
 
-impl Drop for WasiMetricExporter {
+impl Drop for ExporterInner {
     fn drop(&mut self) {
-        if self.export_on_drop {
-            _ = self.export();
+        if self.export_on_drop && !self.is_shutdown.load(Ordering::Relaxed) {
+            _ = self.export(None);
         }
     }
 }
@@ -104,7 +337,55 @@ impl WasiMetricExporter {
     }
 
     /// Exports metric data to a compatible host or component.
+    ///
+    /// Nothing is sent if no instrument has recorded a data point.
+    ///
+    /// Returns `AlreadyShutdown` once the `SdkMeterProvider` the exporter is registered with has
+    /// been shut down.
     pub fn export(&self) -> Result<(), OTelSdkError> {
+        self.inner.export(None)
+    }
+
+    /// Create a meter provider builder with this exporter as its reader and its views registered.
+    pub fn meter_provider_builder(&self) -> MeterProviderBuilder {
+        self.inner
+            .views_unregistered
+            .store(false, Ordering::Relaxed);
+        self.inner.views.iter().cloned().fold(
+            SdkMeterProvider::builder().with_reader(self.clone()),
+            |builder, view| {
+                builder.with_view(move |instrument: &Instrument| view.stream(instrument))
+            },
+        )
+    }
+
+    /// Returns whether views are configured but have not been registered with a provider through
+    /// [`WasiMetricExporter::meter_provider_builder`] or reported as ignored.
+    #[cfg(test)]
+    pub(crate) fn has_unregistered_views(&self) -> bool {
+        self.inner.views_unregistered.load(Ordering::Relaxed)
+    }
+
+    /// Returns the reservoir that instruments of a [`crate::WasiMeterProvider`] sample into.
+    pub(crate) fn exemplar_reservoir(&self) -> Arc<ExemplarReservoir> {
+        self.inner.host.exemplars.clone()
+    }
+
+    /// Returns a handle that does not keep the exporter alive or delay its final export.
+    pub(crate) fn downgrade(&self) -> WeakWasiMetricExporter {
+        WeakWasiMetricExporter(Arc::downgrade(&self.inner))
+    }
+}
+
+impl ExporterInner {
+    fn export(&self, deadline: Option<&Deadline>) -> Result<(), OTelSdkError> {
+        if self.is_shutdown.load(Ordering::Relaxed) {
+            otel_error!(name: "export_already_shutdown", msg = "Shutdown has already been invoked.");
+            return Err(OTelSdkError::AlreadyShutdown);
+        }
+        if self.views_unregistered.swap(false, Ordering::Relaxed) {
+            otel_warn!(name: "metric_views_unregistered", msg = format!("Ignoring {} metric views because the meter provider was not built with WasiMetricExporter::meter_provider_builder", self.views.len()));
+        }
         let mut metrics = ResourceMetrics::default();
         // Scrape the metrics from the reader.
         match self.reader.collect(&mut metrics) {
@@ -124,8 +405,38 @@ impl WasiMetricExporter {
                 }
             },
         }
-        // Export to the host.
-        match wasi::otel::metrics::export(&metrics.into()) {
+        if let Some(deadline) = deadline {
+            if deadline.is_expired() {
+                otel_error!(name: "export_timeout", msg = format!("Operation timed out after {} seconds.", deadline.timeout.as_secs()));
+                return Err(OTelSdkError::Timeout(deadline.timeout));
+            }
+        }
+        self.host.send(&metrics)
+    }
+}
+
+impl HostExport {
+    pub(super) fn send(&self, metrics: &ResourceMetrics) -> OTelSdkResult {
+        #[cfg(feature = "otlp")]
+        let result = if self.otlp_encoding {
+            has_data_points(metrics).then(|| {
+                export_otlp_metrics(&crate::otlp::encode_metrics(
+                    metrics,
+                    self.redactor.as_ref(),
+                    &self.limits,
+                ))
+            })
+        } else {
+            self.send_records(metrics)
+        };
+        #[cfg(not(feature = "otlp"))]
+        let result = self.send_records(metrics);
+        // Skip the host call when there is nothing to send.
+        let Some(result) = result else {
+            return Ok(());
+        };
+        record_instance_export(Signal::Metrics, result.is_ok());
+        match result {
             Ok(_) => Ok(()),
             Err(e) => {
                 otel_error!(name: "export_internal_error", msg = format!("Operation failed due to an internal error: {}", e));
@@ -133,6 +444,112 @@ impl WasiMetricExporter {
             }
         }
     }
+
+    /// Sends metrics as WIT records, along with those kept from failed exports.
+    ///
+    /// Returns `None` without calling the host if no data points are left to send.
+    fn send_records(&self, metrics: &ResourceMetrics) -> Option<Result<(), String>> {
+        let mut records = to_wasi_resource_metrics(metrics, self.redactor.as_ref(), &self.limits);
+        self.exemplars
+            .attach(&mut records, self.redactor.as_ref(), &self.limits);
+        if let Some(state) = &self.state {
+            state.accumulate(&mut records);
+        }
+        if let Some(retry) = &self.retry {
+            retry.prepend(&mut records);
+        }
+        let series = self
+            .unchanged
+            .as_ref()
+            .map(|filter| filter.filter(&mut records));
+        if data_point_count(&records) == 0 {
+            return None;
+        }
+        let result = host_export(&records);
+        match (&result, &self.retry) {
+            (Ok(_), _) => {
+                if let (Some(filter), Some(series)) = (&self.unchanged, series) {
+                    filter.commit(series);
+                }
+            }
+            (Err(_), Some(retry)) => retry.retain(records),
+            (Err(_), None) => record_dropped_metric_points(data_point_count(&records)),
+        }
+        Some(result)
+    }
+}
+
+/// Warns about the builder options that were set but don't apply to OTLP encoded metrics.
+#[cfg(feature = "otlp")]
+pub(super) fn warn_ignored_by_otlp(options: &[(&str, bool)]) {
+    let ignored: Vec<_> = options
+        .iter()
+        .filter(|(_, set)| *set)
+        .map(|(name, _)| *name)
+        .collect();
+    if !ignored.is_empty() {
+        otel_warn!(name: "metric_exporter_otlp_ignored_options", msg = format!("Ignoring {} because they don't apply to OTLP encoded metrics", ignored.join(", ")));
+    }
+}
+
+/// Returns whether any instrument recorded a data point.
+#[cfg(feature = "otlp")]
+fn has_data_points(metrics: &ResourceMetrics) -> bool {
+    use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+
+    fn any<T>(data: &MetricData<T>) -> bool {
+        match data {
+            MetricData::Gauge(g) => g.data_points().next().is_some(),
+            MetricData::Sum(s) => s.data_points().next().is_some(),
+            MetricData::Histogram(h) => h.data_points().next().is_some(),
+            MetricData::ExponentialHistogram(h) => h.data_points().next().is_some(),
+        }
+    }
+    metrics
+        .scope_metrics()
+        .flat_map(|scope| scope.metrics())
+        .any(|metric| match metric.data() {
+            AggregatedMetrics::F64(data) => any(data),
+            AggregatedMetrics::U64(data) => any(data),
+            AggregatedMetrics::I64(data) => any(data),
+        })
+}
+
+/// A timeout measured with the WASI monotonic clock.
+struct Deadline {
+    start: u64,
+    timeout: Duration,
+}
+
+impl Deadline {
+    fn after(timeout: Duration) -> Self {
+        Self {
+            start: now(),
+            timeout,
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        Duration::from_nanos(now().saturating_sub(self.start))
+    }
+
+    fn is_expired(&self) -> bool {
+        self.elapsed() >= self.timeout
+    }
+
+    fn remaining(&self) -> Duration {
+        self.timeout.saturating_sub(self.elapsed())
+    }
+}
+
+/// A weak handle to a [`WasiMetricExporter`].
+#[derive(Debug, Clone)]
+pub(crate) struct WeakWasiMetricExporter(Weak<ExporterInner>);
+
+impl WeakWasiMetricExporter {
+    pub(crate) fn upgrade(&self) -> Option<WasiMetricExporter> {
+        self.0.upgrade().map(|inner| WasiMetricExporter { inner })
+    }
 }
 
 // Unless the `MetricReader` trait is specifically imported in the application code, these methods
@@ -144,29 +561,262 @@ impl MetricReader for WasiMetricExporter {
     /// Delegates to the embedded `ManualReader` to maintain shared ownership
     /// of metric data with an `SdkMeterProvider`.
     fn register_pipeline(&self, pipeline: std::sync::Weak<opentelemetry_sdk::metrics::Pipeline>) {
-        self.reader.register_pipeline(pipeline)
+        self.inner.reader.register_pipeline(pipeline)
     }
 
     /// Collects metrics from all registered instruments into the provided buffer.
     ///
     /// Delegates to the embedded `ManualReader` for the actual collection logic.
     fn collect(&self, rm: &mut ResourceMetrics) -> OTelSdkResult {
-        self.reader.collect(rm)
+        self.inner.reader.collect(rm)
     }
 
+    /// Collects metrics and exports them to the host.
     fn force_flush(&self) -> OTelSdkResult {
-        Ok(())
+        self.inner.export(None)
     }
 
-    fn shutdown(&self) -> OTelSdkResult {
-        Ok(())
+    /// Exports metrics to the host one last time and shuts down the embedded `ManualReader`.
+    ///
+    /// Later exports, including the export on drop, are rejected with `AlreadyShutdown`. The
+    /// final export is abandoned if collecting metrics takes longer than `timeout`.
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        let deadline = Deadline::after(timeout);
+        let result = self.inner.export(Some(&deadline));
+        if let Err(OTelSdkError::AlreadyShutdown) = result {
+            return result;
+        }
+        self.inner.is_shutdown.store(true, Ordering::Relaxed);
+        self.inner
+            .reader
+            .shutdown_with_timeout(deadline.remaining())?;
+        result
     }
 
-    fn shutdown_with_timeout(&self, _timeout: std::time::Duration) -> OTelSdkResult {
-        Ok(())
+    fn temporality(&self, kind: InstrumentKind) -> Temporality {
+        self.inner.reader.temporality(kind)
     }
+}
 
-    fn temporality(&self, kind: InstrumentKind) -> Temporality {
-        self.reader.temporality(kind)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        recording_host::{set_export_error, take_metrics},
+        wit::wasi::otel::metrics::{MetricData, MetricNumber},
+    };
+    use opentelemetry::{metrics::MeterProvider, KeyValue};
+    use opentelemetry_sdk::metrics::SdkMeterProvider;
+
+    #[test]
+    fn data_point_attributes_are_not_limited() {
+        let exporter = WasiMetricExporter::builder()
+            .with_limits(
+                PayloadLimits::builder()
+                    .with_max_attributes(1)
+                    .with_max_attribute_value_length(4)
+                    .build(),
+            )
+            .build();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(exporter.clone())
+            .build();
+        let counter = provider.meter("test").u64_counter("requests").build();
+        counter.add(
+            1,
+            &[KeyValue::new("route", "/orders"), KeyValue::new("a", 1)],
+        );
+        counter.add(
+            1,
+            &[KeyValue::new("route", "/ordinals"), KeyValue::new("a", 1)],
+        );
+
+        exporter.export().unwrap();
+        let exports = take_metrics();
+        let MetricData::U64Sum(sum) = &exports[0].scope_metrics[0].metrics[0].data else {
+            panic!("expected a sum");
+        };
+        // Truncating the route or dropping attributes would merge these series.
+        assert_eq!(sum.data_points.len(), 2);
+        assert!(sum.data_points.iter().all(|p| p.attributes.len() == 2));
+    }
+
+    #[test]
+    fn dropping_a_clone_does_not_export() {
+        let exporter = WasiMetricExporter::default();
+        let clone = exporter.clone();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(exporter.clone())
+            .build();
+        provider
+            .meter("test")
+            .u64_counter("requests")
+            .build()
+            .add(1, &[]);
+
+        drop(clone);
+        assert!(take_metrics().is_empty());
+
+        exporter.export().unwrap();
+        let exports = take_metrics();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0].scope_metrics[0].metrics[0].name, "requests");
+    }
+
+    #[test]
+    fn clones_share_state_until_last_drop() {
+        let exporter = WasiMetricExporter::default();
+        let clone = exporter.clone();
+        let weak = Arc::downgrade(&exporter.inner);
+
+        drop(exporter);
+        assert!(weak.upgrade().is_some());
+        drop(clone);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn export_once_when_every_handle_is_dropped() {
+        let exporter = WasiMetricExporter::default();
+        let clone = exporter.clone();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(exporter.clone())
+            .build();
+        provider
+            .meter("test")
+            .u64_counter("requests")
+            .build()
+            .add(1, &[]);
+
+        drop(exporter);
+        drop(clone);
+        drop(provider);
+        let exports = take_metrics();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0].scope_metrics[0].metrics[0].name, "requests");
+    }
+
+    #[test]
+    fn shutdown_exports_once_and_rejects_later_exports() {
+        let exporter = WasiMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(exporter.clone())
+            .build();
+        let counter = provider.meter("test").u64_counter("requests").build();
+        counter.add(1, &[]);
+
+        provider.force_flush().unwrap();
+        assert_eq!(take_metrics().len(), 1);
+
+        provider.shutdown().unwrap();
+        assert_eq!(take_metrics().len(), 1);
+        assert!(matches!(
+            exporter.export(),
+            Err(OTelSdkError::AlreadyShutdown)
+        ));
+        assert!(matches!(
+            exporter.shutdown_with_timeout(Duration::from_secs(1)),
+            Err(OTelSdkError::AlreadyShutdown)
+        ));
+
+        drop(exporter);
+        drop(provider);
+        assert!(take_metrics().is_empty());
+    }
+
+    #[test]
+    fn shutdown_honors_timeout() {
+        let exporter = WasiMetricExporter::default();
+        let _provider = SdkMeterProvider::builder()
+            .with_reader(exporter.clone())
+            .build();
+
+        assert!(matches!(
+            exporter.shutdown_with_timeout(Duration::ZERO),
+            Err(OTelSdkError::Timeout(_))
+        ));
+        assert!(take_metrics().is_empty());
+        assert!(matches!(
+            exporter.export(),
+            Err(OTelSdkError::AlreadyShutdown)
+        ));
+    }
+
+    #[test]
+    fn retry_buffer_resends_failed_delta_points() {
+        let exporter = WasiMetricExporter::builder()
+            .with_temporality(Temporality::Delta)
+            .with_retry_buffer(RetryBufferLimits::default())
+            .build();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(exporter.clone())
+            .build();
+        let counter = provider.meter("test").u64_counter("requests").build();
+
+        counter.add(2, &[]);
+        set_export_error(Some("host unavailable"));
+        assert!(exporter.export().is_err());
+        set_export_error(None);
+
+        counter.add(3, &[]);
+        exporter.export().unwrap();
+        let exports = take_metrics();
+        assert_eq!(exports.len(), 1);
+        let MetricData::U64Sum(sum) = &exports[0].scope_metrics[0].metrics[0].data else {
+            panic!("expected a u64 sum");
+        };
+        assert!(matches!(sum.data_points[0].value, MetricNumber::U64(5)));
+    }
+
+    #[test]
+    fn skips_exports_without_data_points() {
+        let exporter = WasiMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(exporter.clone())
+            .build();
+        let _counter = provider.meter("test").u64_counter("requests").build();
+
+        exporter.export().unwrap();
+        assert!(take_metrics().is_empty());
+    }
+
+    #[test]
+    fn skips_unchanged_cumulative_series() {
+        let exporter = WasiMetricExporter::builder()
+            .with_skip_unchanged_series()
+            .build();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(exporter.clone())
+            .build();
+        let meter = provider.meter("test");
+        let requests = meter.u64_counter("requests").build();
+        let errors = meter.u64_counter("errors").build();
+        let names = |exports: &[crate::wit::wasi::otel::metrics::ResourceMetrics]| {
+            exports[0].scope_metrics[0]
+                .metrics
+                .iter()
+                .map(|m| m.name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        requests.add(1, &[]);
+        errors.add(1, &[]);
+        exporter.export().unwrap();
+        assert_eq!(names(&take_metrics()), ["requests", "errors"]);
+
+        requests.add(1, &[]);
+        exporter.export().unwrap();
+        assert_eq!(names(&take_metrics()), ["requests"]);
+
+        exporter.export().unwrap();
+        assert!(take_metrics().is_empty());
+
+        // Series are only remembered once the host accepts them.
+        errors.add(1, &[]);
+        set_export_error(Some("host unavailable"));
+        assert!(exporter.export().is_err());
+        set_export_error(None);
+        exporter.export().unwrap();
+        assert_eq!(names(&take_metrics()), ["errors"]);
     }
 }
@@ -0,0 +1,240 @@
+use crate::{
+    metrics::{
+        exemplar::ExemplarReservoir, exporter::HostExport, retry::RetryBuffer,
+        unchanged::UnchangedFilter,
+    },
+    ExemplarFilter, PayloadLimits, Redactor, RetryBufferLimits,
+};
+use opentelemetry::otel_error;
+use opentelemetry_sdk::{
+    error::{OTelSdkError, OTelSdkResult},
+    metrics::{data::ResourceMetrics, exporter::PushMetricExporter, Temporality},
+};
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// A push metric exporter that sends metrics to a WASI host.
+///
+/// Unlike [`WasiMetricExporter`], which collects metrics itself, this exporter is driven by a
+/// reader such as `PeriodicReader` or a custom one. Each export makes a synchronous
+/// `wasi:otel/metrics.export` call, so readers that don't need a background thread should prefer
+/// [`WasiMetricExporter`].
+///
+/// # Example
+/// ```ignore
+/// let exporter = WasiPushMetricExporter::builder()
+///     .with_temporality(Temporality::Delta)
+///     .build();
+/// let reader = PeriodicReader::builder(exporter).build();
+/// let provider = SdkMeterProvider::builder().with_reader(reader).build();
+/// ```
+///
+/// [`WasiMetricExporter`]: crate::WasiMetricExporter
+#[derive(Debug)]
+pub struct WasiPushMetricExporter {
+    is_shutdown: AtomicBool,
+    host: HostExport,
+    temporality: Temporality,
+}
+
+#[derive(Default)]
+pub struct WasiPushMetricExporterBuilder {
+    redactor: Option<Redactor>,
+    limits: PayloadLimits,
+    temporality: Temporality,
+    retry: Option<RetryBufferLimits>,
+    skip_unchanged: bool,
+    #[cfg(feature = "otlp")]
+    otlp_encoding: bool,
+}
+
+impl WasiPushMetricExporterBuilder {
+    /// Redact data point and exemplar attributes before they are sent to the host.
+    #[cfg(feature = "redaction")]
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+
+    /// Truncate data point and exemplar attributes that exceed `limits`.
+    pub fn with_limits(mut self, limits: PayloadLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Set the temporality the reader aggregates metrics with. Defaults to cumulative.
+    pub fn with_temporality(mut self, temporality: Temporality) -> Self {
+        self.temporality = temporality;
+        self
+    }
+
+    /// Keep metrics the host rejects and send them again with the next export.
+    ///
+    /// See [`WasiMetricExporterBuilder::with_retry_buffer`] for how kept data is merged.
+    ///
+    /// [`WasiMetricExporterBuilder::with_retry_buffer`]: crate::WasiMetricExporterBuilder::with_retry_buffer
+    pub fn with_retry_buffer(mut self, limits: RetryBufferLimits) -> Self {
+        self.retry = Some(limits);
+        self
+    }
+
+    /// Leave cumulative series out of an export if they haven't changed since the last
+    /// successful export.
+    ///
+    /// See [`WasiMetricExporterBuilder::with_skip_unchanged_series`] for details.
+    ///
+    /// [`WasiMetricExporterBuilder::with_skip_unchanged_series`]: crate::WasiMetricExporterBuilder::with_skip_unchanged_series
+    pub fn with_skip_unchanged_series(mut self) -> Self {
+        self.skip_unchanged = true;
+        self
+    }
+
+    /// Send metrics to the host as an encoded OTLP `ExportMetricsServiceRequest` through the
+    /// `wasi:otel/otlp` interface instead of as WIT records.
+    ///
+    /// The redactor and limits are still applied before encoding. The retry buffer and skipping
+    /// of unchanged series only work on WIT records, so `build` ignores them with a warning.
+    #[cfg(feature = "otlp")]
+    pub fn with_otlp_encoding(mut self) -> Self {
+        self.otlp_encoding = true;
+        self
+    }
+
+    /// Build the exporter.
+    #[cfg_attr(not(feature = "otlp"), allow(unused_mut))]
+    pub fn build(mut self) -> WasiPushMetricExporter {
+        #[cfg(feature = "otlp")]
+        if self.otlp_encoding {
+            super::exporter::warn_ignored_by_otlp(&[
+                ("with_retry_buffer", self.retry.take().is_some()),
+                (
+                    "with_skip_unchanged_series",
+                    std::mem::take(&mut self.skip_unchanged),
+                ),
+            ]);
+        }
+        WasiPushMetricExporter {
+            is_shutdown: AtomicBool::new(false),
+            host: HostExport {
+                redactor: self.redactor,
+                limits: self.limits,
+                retry: self.retry.map(RetryBuffer::new),
+                unchanged: self.skip_unchanged.then(UnchangedFilter::default),
+                state: None,
+                exemplars: Arc::new(ExemplarReservoir::new(
+                    ExemplarFilter::AlwaysOff,
+                    0,
+                    Vec::new(),
+                )),
+                #[cfg(feature = "otlp")]
+                otlp_encoding: self.otlp_encoding,
+            },
+            temporality: self.temporality,
+        }
+    }
+}
+
+impl WasiPushMetricExporter {
+    /// Create a new `WasiPushMetricExporter`.
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    /// Create a new builder for configuring a WasiPushMetricExporter.
+    pub fn builder() -> WasiPushMetricExporterBuilder {
+        WasiPushMetricExporterBuilder::default()
+    }
+}
+
+impl Default for WasiPushMetricExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PushMetricExporter for WasiPushMetricExporter {
+    fn export(&self, metrics: &ResourceMetrics) -> impl Future<Output = OTelSdkResult> + Send {
+        let result = if self.is_shutdown.load(Ordering::Relaxed) {
+            otel_error!(name: "export_already_shutdown", msg = "Shutdown has already been invoked.");
+            Err(OTelSdkError::AlreadyShutdown)
+        } else {
+            self.host.send(metrics)
+        };
+        std::future::ready(result)
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+
+    fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+        if self.is_shutdown.swap(true, Ordering::Relaxed) {
+            return Err(OTelSdkError::AlreadyShutdown);
+        }
+        Ok(())
+    }
+
+    fn temporality(&self) -> Temporality {
+        self.temporality
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{recording_host::take_metrics, WasiMetricExporter};
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry_sdk::metrics::{reader::MetricReader, SdkMeterProvider};
+    use std::{
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    fn export(exporter: &WasiPushMetricExporter, metrics: &ResourceMetrics) -> OTelSdkResult {
+        let mut cx = Context::from_waker(Waker::noop());
+        match pin!(exporter.export(metrics)).poll(&mut cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => panic!("export should complete without waiting"),
+        }
+    }
+
+    #[test]
+    fn exports_collected_metrics_until_shutdown() {
+        let exporter = WasiPushMetricExporter::builder()
+            .with_temporality(Temporality::Delta)
+            .build();
+        assert_eq!(exporter.temporality(), Temporality::Delta);
+
+        let reader = WasiMetricExporter::builder()
+            .with_manual_export_only()
+            .build();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .build();
+        provider
+            .meter("test")
+            .u64_counter("requests")
+            .build()
+            .add(1, &[]);
+        let mut metrics = ResourceMetrics::default();
+        reader.collect(&mut metrics).unwrap();
+
+        export(&exporter, &metrics).unwrap();
+        let exports = take_metrics();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0].scope_metrics[0].metrics[0].name, "requests");
+
+        exporter.shutdown().unwrap();
+        assert!(matches!(
+            export(&exporter, &metrics),
+            Err(OTelSdkError::AlreadyShutdown)
+        ));
+        assert!(take_metrics().is_empty());
+    }
+}
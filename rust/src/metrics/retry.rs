@@ -0,0 +1,514 @@
+use crate::{health::record_dropped_metric_points, wit::wasi::otel::metrics::*};
+use std::sync::Mutex;
+
+/// Limits on the metrics a metric exporter keeps to resend after the host rejects an export.
+///
+/// Defaults to 4096 data points and 1 MiB. Sizes are estimated from the metric data, so the
+/// memory used by the buffer may differ slightly.
+///
+/// # Example
+/// ```ignore
+/// let exporter = WasiMetricExporter::builder()
+///     .with_temporality(Temporality::Delta)
+///     .with_retry_buffer(
+///         RetryBufferLimits::builder()
+///             .with_max_data_points(1024)
+///             .build(),
+///     )
+///     .build();
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct RetryBufferLimits {
+    max_data_points: usize,
+    max_bytes: usize,
+}
+
+impl Default for RetryBufferLimits {
+    fn default() -> Self {
+        Self {
+            max_data_points: 4096,
+            max_bytes: 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RetryBufferLimitsBuilder {
+    limits: RetryBufferLimits,
+}
+
+impl RetryBufferLimitsBuilder {
+    /// The maximum number of data points kept for the next export.
+    pub fn with_max_data_points(mut self, max: usize) -> Self {
+        self.limits.max_data_points = max;
+        self
+    }
+
+    /// The maximum estimated size in bytes of the metrics kept for the next export.
+    pub fn with_max_bytes(mut self, max: usize) -> Self {
+        self.limits.max_bytes = max;
+        self
+    }
+
+    /// Build the limits.
+    pub fn build(self) -> RetryBufferLimits {
+        self.limits
+    }
+}
+
+impl RetryBufferLimits {
+    /// Create a new builder for configuring RetryBufferLimits.
+    pub fn builder() -> RetryBufferLimitsBuilder {
+        RetryBufferLimitsBuilder::default()
+    }
+}
+
+/// Holds metrics the host rejected so they can be sent with the next export.
+#[derive(Debug)]
+pub(crate) struct RetryBuffer {
+    limits: RetryBufferLimits,
+    pending: Mutex<Option<ResourceMetrics>>,
+}
+
+impl RetryBuffer {
+    pub(crate) fn new(limits: RetryBufferLimits) -> Self {
+        Self {
+            limits,
+            pending: Mutex::default(),
+        }
+    }
+
+    /// Folds the metrics kept from failed exports into `metrics`.
+    pub(crate) fn prepend(&self, metrics: &mut ResourceMetrics) {
+        if let Some(pending) = self.pending.lock().unwrap().take() {
+            record_dropped_metric_points(merge(pending, metrics));
+        }
+    }
+
+    /// Keeps metrics the host rejected, dropping whole metrics until they fit within the limits.
+    pub(crate) fn retain(&self, mut metrics: ResourceMetrics) {
+        record_dropped_metric_points(trim(&mut metrics, &self.limits));
+        *self.pending.lock().unwrap() = (!metrics.scope_metrics.is_empty()).then_some(metrics);
+    }
+}
+
+/// Returns the number of data points in a payload.
+pub(crate) fn data_point_count(metrics: &ResourceMetrics) -> usize {
+    metrics
+        .scope_metrics
+        .iter()
+        .flat_map(|scope| &scope.metrics)
+        .map(|metric| metric_data_points(&metric.data).0)
+        .sum()
+}
+
+/// Folds the metrics of an `older` payload into `newer`, returning the number of data points that
+/// couldn't be merged.
+///
+/// Delta sums and histograms of the same series are added together. For cumulative data and
+/// gauges the newer data point replaces the older one.
+fn merge(older: ResourceMetrics, newer: &mut ResourceMetrics) -> usize {
+    let mut dropped = 0;
+    for scope in older.scope_metrics {
+        let Some(target) = newer
+            .scope_metrics
+            .iter_mut()
+            .find(|target| same_scope(&target.scope, &scope.scope))
+        else {
+            newer.scope_metrics.push(scope);
+            continue;
+        };
+        for metric in scope.metrics {
+            match target.metrics.iter_mut().find(|m| m.name == metric.name) {
+                Some(existing) => dropped += merge_data(metric.data, &mut existing.data),
+                None => target.metrics.push(metric),
+            }
+        }
+    }
+    dropped
+}
+
+fn same_scope(a: &InstrumentationScope, b: &InstrumentationScope) -> bool {
+    a.name == b.name && a.version == b.version && a.schema_url == b.schema_url
+}
+
+fn merge_data(older: MetricData, newer: &mut MetricData) -> usize {
+    use MetricData::*;
+    match (older, newer) {
+        (F64Gauge(o), F64Gauge(n)) | (U64Gauge(o), U64Gauge(n)) | (S64Gauge(o), S64Gauge(n)) => {
+            merge_points(o.data_points, &mut n.data_points, |_, _| true)
+        }
+        (F64Sum(o), F64Sum(n)) | (U64Sum(o), U64Sum(n)) | (S64Sum(o), S64Sum(n)) => {
+            if !is_delta(o.temporality, n.temporality) {
+                return merge_points(o.data_points, &mut n.data_points, |_, _| true);
+            }
+            n.start_time = o.start_time;
+            merge_points(o.data_points, &mut n.data_points, |o, n| {
+                if !add_numbers(&o.value, &mut n.value) {
+                    return false;
+                }
+                n.exemplars.extend(o.exemplars);
+                true
+            })
+        }
+        (F64Histogram(o), F64Histogram(n))
+        | (U64Histogram(o), U64Histogram(n))
+        | (S64Histogram(o), S64Histogram(n)) => {
+            if !is_delta(o.temporality, n.temporality) {
+                return merge_points(o.data_points, &mut n.data_points, |_, _| true);
+            }
+            n.start_time = o.start_time;
+            merge_points(o.data_points, &mut n.data_points, |o, n| {
+                if o.bounds != n.bounds || !add_numbers(&o.sum, &mut n.sum) {
+                    return false;
+                }
+                n.count += o.count;
+                for (n, o) in n.bucket_counts.iter_mut().zip(o.bucket_counts) {
+                    *n += o;
+                }
+                merge_min_max(o.min, o.max, &mut n.min, &mut n.max);
+                n.exemplars.extend(o.exemplars);
+                true
+            })
+        }
+        (F64ExponentialHistogram(o), F64ExponentialHistogram(n))
+        | (U64ExponentialHistogram(o), U64ExponentialHistogram(n))
+        | (S64ExponentialHistogram(o), S64ExponentialHistogram(n)) => {
+            if !is_delta(o.temporality, n.temporality) {
+                return merge_points(o.data_points, &mut n.data_points, |_, _| true);
+            }
+            n.start_time = o.start_time;
+            merge_points(o.data_points, &mut n.data_points, |o, n| {
+                if o.scale != n.scale
+                    || o.zero_threshold != n.zero_threshold
+                    || !add_numbers(&o.sum, &mut n.sum)
+                {
+                    return false;
+                }
+                n.count += o.count;
+                n.zero_count += o.zero_count;
+                merge_buckets(o.positive_bucket, &mut n.positive_bucket);
+                merge_buckets(o.negative_bucket, &mut n.negative_bucket);
+                merge_min_max(o.min, o.max, &mut n.min, &mut n.max);
+                n.exemplars.extend(o.exemplars);
+                true
+            })
+        }
+        // The instrument changed type, so the older data can't be reported alongside it.
+        (older, _) => metric_data_points(&older).0,
+    }
+}
+
+fn is_delta(older: Temporality, newer: Temporality) -> bool {
+    matches!(older, Temporality::Delta) && matches!(newer, Temporality::Delta)
+}
+
+/// Merges the data points of matching series with `combine`, which returns false if they can't
+/// be merged. Series only present in `older` are kept as they are.
+fn merge_points<P: DataPoint>(
+    older: Vec<P>,
+    newer: &mut Vec<P>,
+    mut combine: impl FnMut(P, &mut P) -> bool,
+) -> usize {
+    let mut dropped = 0;
+    for point in older {
+        match newer
+            .iter_mut()
+            .find(|n| same_attributes(n.attributes(), point.attributes()))
+        {
+            Some(existing) => dropped += usize::from(!combine(point, existing)),
+            None => newer.push(point),
+        }
+    }
+    dropped
+}
+
+fn same_attributes(a: &[KeyValue], b: &[KeyValue]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .all(|kv| b.iter().any(|o| o.key == kv.key && o.value == kv.value))
+}
+
+pub(super) fn add_numbers(older: &MetricNumber, newer: &mut MetricNumber) -> bool {
+    match (older, newer) {
+        (MetricNumber::F64(o), MetricNumber::F64(n)) => *n += o,
+        (MetricNumber::S64(o), MetricNumber::S64(n)) => *n = n.saturating_add(*o),
+        (MetricNumber::U64(o), MetricNumber::U64(n)) => *n = n.saturating_add(*o),
+        _ => return false,
+    }
+    true
+}
+
+pub(super) fn merge_min_max(
+    older_min: Option<MetricNumber>,
+    older_max: Option<MetricNumber>,
+    min: &mut Option<MetricNumber>,
+    max: &mut Option<MetricNumber>,
+) {
+    if keep_older(&older_min, min, |o, n| o < n) {
+        *min = older_min;
+    }
+    if keep_older(&older_max, max, |o, n| o > n) {
+        *max = older_max;
+    }
+}
+
+fn keep_older(
+    older: &Option<MetricNumber>,
+    newer: &Option<MetricNumber>,
+    better: impl Fn(f64, f64) -> bool,
+) -> bool {
+    match (older, newer) {
+        (Some(o), Some(n)) => better(as_f64(o), as_f64(n)),
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
+
+fn as_f64(number: &MetricNumber) -> f64 {
+    match *number {
+        MetricNumber::F64(v) => v,
+        MetricNumber::S64(v) => v as f64,
+        MetricNumber::U64(v) => v as f64,
+    }
+}
+
+fn merge_buckets(older: ExponentialBucket, newer: &mut ExponentialBucket) {
+    if older.counts.is_empty() {
+        return;
+    }
+    if newer.counts.is_empty() {
+        *newer = older;
+        return;
+    }
+    let end = |b: &ExponentialBucket| b.offset as i64 + b.counts.len() as i64;
+    let offset = older.offset.min(newer.offset);
+    let mut counts = vec![0; (end(&older).max(end(newer)) - offset as i64) as usize];
+    for bucket in [&older, &*newer] {
+        let start = (bucket.offset - offset) as usize;
+        for (total, count) in counts[start..].iter_mut().zip(&bucket.counts) {
+            *total += count;
+        }
+    }
+    *newer = ExponentialBucket { offset, counts };
+}
+
+/// Drops whole metrics from the end of the payload until it fits within `limits`, returning the
+/// number of data points dropped.
+fn trim(metrics: &mut ResourceMetrics, limits: &RetryBufferLimits) -> usize {
+    let (mut points, mut bytes) = metrics
+        .scope_metrics
+        .iter()
+        .flat_map(|scope| &scope.metrics)
+        .map(metric_size)
+        .fold((0, 0), |(p, b), (mp, mb)| (p + mp, b + mb));
+    let mut dropped = 0;
+    while points > limits.max_data_points || bytes > limits.max_bytes {
+        let Some(scope) = metrics.scope_metrics.last_mut() else {
+            break;
+        };
+        match scope.metrics.pop() {
+            Some(metric) => {
+                let (metric_points, metric_bytes) = metric_size(&metric);
+                points -= metric_points;
+                bytes -= metric_bytes;
+                dropped += metric_points;
+            }
+            None => _ = metrics.scope_metrics.pop(),
+        }
+    }
+    dropped
+}
+
+/// Returns the number of data points in a metric and its estimated size in bytes.
+fn metric_size(metric: &Metric) -> (usize, usize) {
+    let (points, bytes) = metric_data_points(&metric.data);
+    (
+        points,
+        metric.name.len() + metric.description.len() + metric.unit.len() + bytes,
+    )
+}
+
+/// Returns the number of data points in metric data and their estimated size in bytes.
+pub(super) fn metric_data_points(data: &MetricData) -> (usize, usize) {
+    fn sized<P: DataPoint>(points: &[P]) -> (usize, usize) {
+        (points.len(), points.iter().map(DataPoint::size).sum())
+    }
+    use MetricData::*;
+    match data {
+        F64Gauge(g) | U64Gauge(g) | S64Gauge(g) => sized(&g.data_points),
+        F64Sum(s) | U64Sum(s) | S64Sum(s) => sized(&s.data_points),
+        F64Histogram(h) | U64Histogram(h) | S64Histogram(h) => sized(&h.data_points),
+        F64ExponentialHistogram(h) | U64ExponentialHistogram(h) | S64ExponentialHistogram(h) => {
+            sized(&h.data_points)
+        }
+    }
+}
+
+/// The size of the fixed width fields of a data point or exemplar.
+const FIXED_SIZE: usize = 64;
+
+pub(super) trait DataPoint {
+    fn attributes(&self) -> &[KeyValue];
+    fn exemplars(&self) -> &[Exemplar];
+    fn exemplars_mut(&mut self) -> &mut Vec<Exemplar>;
+    fn extra_size(&self) -> usize {
+        0
+    }
+
+    fn size(&self) -> usize {
+        FIXED_SIZE
+            + attributes_size(self.attributes())
+            + self
+                .exemplars()
+                .iter()
+                .map(|e| FIXED_SIZE + attributes_size(&e.filtered_attributes))
+                .sum::<usize>()
+            + self.extra_size()
+    }
+}
+
+fn attributes_size(attributes: &[KeyValue]) -> usize {
+    attributes
+        .iter()
+        .map(|kv| kv.key.len() + kv.value.len())
+        .sum()
+}
+
+macro_rules! impl_data_point {
+    ($($point:ty => |$p:ident| $extra:expr),* $(,)?) => {
+        $(
+            impl DataPoint for $point {
+                fn attributes(&self) -> &[KeyValue] {
+                    &self.attributes
+                }
+
+                fn exemplars(&self) -> &[Exemplar] {
+                    &self.exemplars
+                }
+
+                fn exemplars_mut(&mut self) -> &mut Vec<Exemplar> {
+                    &mut self.exemplars
+                }
+
+                fn extra_size(&self) -> usize {
+                    let $p = self;
+                    $extra
+                }
+            }
+        )*
+    };
+}
+
+impl_data_point!(
+    GaugeDataPoint => |_p| 0,
+    SumDataPoint => |_p| 0,
+    HistogramDataPoint => |p| (p.bounds.len() + p.bucket_counts.len()) * 8,
+    ExponentialHistogramDataPoint => |p| {
+        (p.positive_bucket.counts.len() + p.negative_bucket.counts.len()) * 8
+    },
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wit::wasi::clocks::wall_clock::Datetime;
+
+    fn time(seconds: u64) -> Datetime {
+        Datetime {
+            seconds,
+            nanoseconds: 0,
+        }
+    }
+
+    fn sum(name: &str, temporality: Temporality, points: &[(&str, u64)]) -> Metric {
+        Metric {
+            name: name.to_string(),
+            description: String::new(),
+            unit: String::new(),
+            data: MetricData::U64Sum(Sum {
+                data_points: points
+                    .iter()
+                    .map(|(route, value)| SumDataPoint {
+                        attributes: vec![KeyValue {
+                            key: "route".to_string(),
+                            value: route.to_string(),
+                        }],
+                        value: MetricNumber::U64(*value),
+                        exemplars: Vec::new(),
+                    })
+                    .collect(),
+                start_time: time(0),
+                time: time(10),
+                temporality,
+                is_monotonic: true,
+            }),
+        }
+    }
+
+    fn payload(metrics: Vec<Metric>) -> ResourceMetrics {
+        ResourceMetrics {
+            resource: crate::wit::wasi::otel::types::Resource {
+                attributes: Vec::new(),
+                schema_url: None,
+            },
+            scope_metrics: vec![ScopeMetrics {
+                scope: InstrumentationScope {
+                    name: "test".to_string(),
+                    version: None,
+                    schema_url: None,
+                    attributes: Vec::new(),
+                },
+                metrics,
+            }],
+        }
+    }
+
+    fn values(metric: &Metric) -> Vec<(String, u64)> {
+        let MetricData::U64Sum(sum) = &metric.data else {
+            panic!("expected a u64 sum");
+        };
+        sum.data_points
+            .iter()
+            .map(|p| match p.value {
+                MetricNumber::U64(v) => (p.attributes[0].value.clone(), v),
+                _ => panic!("expected a u64 value"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merges_delta_series_and_replaces_cumulative_series() {
+        let older = payload(vec![
+            sum("delta", Temporality::Delta, &[("/a", 1), ("/b", 2)]),
+            sum("cumulative", Temporality::Cumulative, &[("/a", 1)]),
+            sum("stale", Temporality::Delta, &[("/a", 5)]),
+        ]);
+        let mut newer = payload(vec![
+            sum("delta", Temporality::Delta, &[("/a", 10)]),
+            sum("cumulative", Temporality::Cumulative, &[("/a", 3)]),
+        ]);
+
+        assert_eq!(merge(older, &mut newer), 0);
+        let metrics = &newer.scope_metrics[0].metrics;
+        assert_eq!(
+            values(&metrics[0]),
+            [("/a".to_string(), 11), ("/b".to_string(), 2)]
+        );
+        assert_eq!(values(&metrics[1]), [("/a".to_string(), 3)]);
+        assert_eq!(metrics[2].name, "stale");
+    }
+
+    #[test]
+    fn trims_whole_metrics_to_fit_limits() {
+        let mut metrics = payload(vec![
+            sum("first", Temporality::Delta, &[("/a", 1), ("/b", 1)]),
+            sum("second", Temporality::Delta, &[("/a", 1), ("/b", 1)]),
+        ]);
+        let limits = RetryBufferLimits::builder().with_max_data_points(3).build();
+
+        assert_eq!(trim(&mut metrics, &limits), 2);
+        assert_eq!(data_point_count(&metrics), 2);
+        assert_eq!(metrics.scope_metrics[0].metrics[0].name, "first");
+    }
+}
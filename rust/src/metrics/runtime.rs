@@ -0,0 +1,273 @@
+use crate::health::{dropped_metric_points, dropped_value_elements, instance_exports, Signal};
+use opentelemetry::{
+    metrics::{Meter, MeterProvider, ObservableCounter, ObservableGauge},
+    InstrumentationScope, KeyValue,
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+/// Observable instruments that report the health of the component instance.
+///
+/// The instruments are sampled every time metrics are collected, e.g. when
+/// `WasiMetricExporter::export` runs. The following metrics are reported:
+///
+/// - `wasi.component.memory.size`: The size of the instance's linear memory, in bytes. Only
+///   reported on `wasm32` targets.
+/// - `wasi.component.allocator.usage`: The number of bytes currently allocated. Only reported when
+///   [`WasiTrackingAllocator`] is the global allocator.
+/// - `wasi.component.uptime`: The time since the runtime metrics were registered, in seconds.
+/// - `wasi.component.requests`: The number of requests recorded with
+///   [`WasiRuntimeMetrics::record_request`].
+/// - `wasi.component.telemetry.exports`: The number of exports to the host, by `signal` and
+///   `outcome`.
+/// - `wasi.component.telemetry.dropped_data_points`: The number of metric data points that could
+///   not be delivered to the host, because an export failed and they didn't fit in the retry
+///   buffer or couldn't be merged with newer data.
+/// - `wasi.component.telemetry.dropped_value_elements`: The number of array elements and map
+///   entries dropped from attribute values and log bodies by the array length limit of
+///   [`PayloadLimits`](crate::PayloadLimits).
+///
+/// # Example
+/// ```ignore
+/// let runtime_metrics = WasiRuntimeMetrics::register(&meter_provider);
+/// runtime_metrics.record_request();
+/// ```
+#[derive(Debug, Clone)]
+pub struct WasiRuntimeMetrics {
+    requests: Arc<AtomicU64>,
+    _instruments: Arc<RuntimeInstruments>,
+}
+
+#[derive(Debug)]
+struct RuntimeInstruments {
+    _memory_size: Option<ObservableGauge<u64>>,
+    _allocator_usage: ObservableGauge<u64>,
+    _uptime: ObservableGauge<f64>,
+    _requests: ObservableCounter<u64>,
+    _telemetry_exports: ObservableCounter<u64>,
+    _dropped_data_points: ObservableCounter<u64>,
+    _dropped_value_elements: ObservableCounter<u64>,
+}
+
+impl WasiRuntimeMetrics {
+    /// Register the runtime instruments with `provider`.
+    pub fn register(provider: &impl MeterProvider) -> Self {
+        let meter = provider.meter_with_scope(
+            InstrumentationScope::builder(env!("CARGO_PKG_NAME"))
+                .with_version(env!("CARGO_PKG_VERSION"))
+                .build(),
+        );
+        let requests = Arc::new(AtomicU64::new(0));
+        let instruments = RuntimeInstruments {
+            _memory_size: memory_size_gauge(&meter),
+            _allocator_usage: meter
+                .u64_observable_gauge("wasi.component.allocator.usage")
+                .with_description("The number of bytes currently allocated.")
+                .with_unit("By")
+                .with_callback(|observer| {
+                    if let Some(usage) = WasiTrackingAllocator::usage() {
+                        observer.observe(usage as u64, &[]);
+                    }
+                })
+                .build(),
+            _uptime: {
+                let start = Instant::now();
+                meter
+                    .f64_observable_gauge("wasi.component.uptime")
+                    .with_description("The time since the runtime metrics were registered.")
+                    .with_unit("s")
+                    .with_callback(move |observer| {
+                        observer.observe(start.elapsed().as_secs_f64(), &[]);
+                    })
+                    .build()
+            },
+            _requests: {
+                let requests = requests.clone();
+                meter
+                    .u64_observable_counter("wasi.component.requests")
+                    .with_description("The number of requests handled by the component instance.")
+                    .with_unit("{request}")
+                    .with_callback(move |observer| {
+                        observer.observe(requests.load(Ordering::Relaxed), &[]);
+                    })
+                    .build()
+            },
+            _telemetry_exports: meter
+                .u64_observable_counter("wasi.component.telemetry.exports")
+                .with_description("The number of telemetry exports to the host.")
+                .with_unit("{export}")
+                .with_callback(|observer| {
+                    for signal in Signal::ALL {
+                        let (succeeded, failed) = instance_exports(signal);
+                        for (outcome, count) in [("success", succeeded), ("failure", failed)] {
+                            observer.observe(
+                                count,
+                                &[
+                                    KeyValue::new("signal", signal.name()),
+                                    KeyValue::new("outcome", outcome),
+                                ],
+                            );
+                        }
+                    }
+                })
+                .build(),
+            _dropped_data_points: meter
+                .u64_observable_counter("wasi.component.telemetry.dropped_data_points")
+                .with_description("The number of metric data points lost after failed exports.")
+                .with_unit("{data_point}")
+                .with_callback(|observer| observer.observe(dropped_metric_points(), &[]))
+                .build(),
+            _dropped_value_elements: meter
+                .u64_observable_counter("wasi.component.telemetry.dropped_value_elements")
+                .with_description(
+                    "The number of array elements and map entries dropped by payload limits.",
+                )
+                .with_unit("{element}")
+                .with_callback(|observer| observer.observe(dropped_value_elements(), &[]))
+                .build(),
+        };
+        Self {
+            requests,
+            _instruments: Arc::new(instruments),
+        }
+    }
+
+    /// Count a request handled by the component instance.
+    pub fn record_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn memory_size_gauge(meter: &Meter) -> Option<ObservableGauge<u64>> {
+    Some(
+        meter
+            .u64_observable_gauge("wasi.component.memory.size")
+            .with_description("The size of the component instance's linear memory.")
+            .with_unit("By")
+            .with_callback(|observer| {
+                const PAGE_SIZE: u64 = 64 * 1024;
+                observer.observe(core::arch::wasm32::memory_size(0) as u64 * PAGE_SIZE, &[]);
+            })
+            .build(),
+    )
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn memory_size_gauge(_meter: &Meter) -> Option<ObservableGauge<u64>> {
+    None
+}
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+/// A global allocator that tracks the number of bytes in use for [`WasiRuntimeMetrics`].
+///
+/// # Example
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOCATOR: WasiTrackingAllocator = WasiTrackingAllocator::new(std::alloc::System);
+/// ```
+#[derive(Debug, Default)]
+pub struct WasiTrackingAllocator<A = System> {
+    inner: A,
+}
+
+impl<A> WasiTrackingAllocator<A> {
+    /// Track the allocations made by `inner`.
+    pub const fn new(inner: A) -> Self {
+        Self { inner }
+    }
+}
+
+impl WasiTrackingAllocator {
+    /// The number of bytes currently allocated, if a tracking allocator is in use.
+    ///
+    /// A component that allocates at all has a non-zero usage by the time metrics are collected,
+    /// so a usage of zero means no tracking allocator is installed.
+    fn usage() -> Option<usize> {
+        Some(ALLOCATED.load(Ordering::Relaxed)).filter(|&usage| usage != 0)
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for WasiTrackingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc(layout);
+        if !ptr.is_null() {
+            ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = self.inner.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+            ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        }
+        new_ptr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        recording_host::take_metrics,
+        wit::wasi::otel::metrics::{MetricData, MetricNumber},
+        WasiMetricExporter,
+    };
+    use opentelemetry_sdk::metrics::SdkMeterProvider;
+
+    #[test]
+    fn runtime_metrics_are_sampled_on_export() {
+        let exporter = WasiMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(exporter.clone())
+            .build();
+        let runtime_metrics = WasiRuntimeMetrics::register(&provider);
+        runtime_metrics.record_request();
+        runtime_metrics.record_request();
+
+        exporter.export().unwrap();
+        let exports = take_metrics();
+        let metrics = &exports[0].scope_metrics[0].metrics;
+        let names: Vec<_> = metrics.iter().map(|m| m.name.as_str()).collect();
+        assert!(names.contains(&"wasi.component.uptime"));
+        assert!(names.contains(&"wasi.component.telemetry.exports"));
+        let requests = metrics
+            .iter()
+            .find(|m| m.name == "wasi.component.requests")
+            .unwrap();
+        let MetricData::U64Sum(sum) = &requests.data else {
+            panic!("unexpected data {:?}", requests.data);
+        };
+        assert!(matches!(sum.data_points[0].value, MetricNumber::U64(2)));
+    }
+
+    #[test]
+    fn tracking_allocator_counts_bytes_in_use() {
+        let allocator = WasiTrackingAllocator::new(System);
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        let before = ALLOCATED.load(Ordering::Relaxed);
+        unsafe {
+            let ptr = allocator.alloc(layout);
+            assert_eq!(ALLOCATED.load(Ordering::Relaxed) - before, 64);
+            assert!(WasiTrackingAllocator::usage().is_some());
+            let ptr = allocator.realloc(ptr, layout, 128);
+            assert_eq!(ALLOCATED.load(Ordering::Relaxed) - before, 128);
+            allocator.dealloc(ptr, Layout::from_size_align(128, 8).unwrap());
+        }
+        assert_eq!(ALLOCATED.load(Ordering::Relaxed), before);
+    }
+}
@@ -0,0 +1,486 @@
+use crate::{
+    metrics::{
+        retry::{add_numbers, merge_min_max},
+        unchanged::{series_key, SeriesKey},
+    },
+    wit::wasi::{clocks::wall_clock::Datetime, otel::metrics::*},
+};
+use opentelemetry::{otel_error, otel_warn};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt};
+
+/// A key-value store that cumulative metric totals are persisted to.
+///
+/// Implemented for `spin_sdk::key_value::Store` when the `spin` feature is enabled. Implement it
+/// for other stores to persist metric state elsewhere.
+pub trait MetricStateStore: Send + Sync {
+    /// Returns the value stored under `key`, if any.
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, String>;
+
+    /// Stores `value` under `key`, replacing any previous value.
+    fn set(&self, key: &str, value: &[u8]) -> Result<(), String>;
+}
+
+#[cfg(feature = "spin")]
+impl MetricStateStore for spin_sdk::key_value::Store {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        spin_sdk::key_value::Store::get(self, key).map_err(|e| e.to_string())
+    }
+
+    fn set(&self, key: &str, value: &[u8]) -> Result<(), String> {
+        spin_sdk::key_value::Store::set(self, key, value).map_err(|e| e.to_string())
+    }
+}
+
+/// Turns the delta sums and histograms of each export into cumulative series by adding them to
+/// totals kept in a [`MetricStateStore`].
+///
+/// At most `max_series` series are kept. Beyond that, the series updated least recently are
+/// evicted and restart from their next delta.
+pub(crate) struct CumulativeState {
+    store: Box<dyn MetricStateStore>,
+    key: String,
+    max_series: usize,
+}
+
+impl fmt::Debug for CumulativeState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CumulativeState")
+            .field("key", &self.key)
+            .field("max_series", &self.max_series)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CumulativeState {
+    pub(crate) fn new(store: Box<dyn MetricStateStore>, key: String, max_series: usize) -> Self {
+        Self {
+            store,
+            key,
+            max_series,
+        }
+    }
+
+    /// Adds the delta sums and histograms in `metrics` to the persisted totals and reports the
+    /// totals instead.
+    ///
+    /// If the totals can't be loaded, the metrics are left as deltas.
+    pub(crate) fn accumulate(&self, metrics: &mut ResourceMetrics) {
+        let has_deltas = metrics
+            .scope_metrics
+            .iter()
+            .flat_map(|scope| &scope.metrics)
+            .any(|metric| is_delta(&metric.data));
+        if !has_deltas {
+            return;
+        }
+        let (generation, mut totals) = match self.load() {
+            Ok((generation, totals)) => (generation + 1, totals),
+            Err(e) => {
+                otel_error!(name: "metric_state_load_failed", msg = format!("Operation failed due to an internal error: {}", e));
+                return;
+            }
+        };
+        for scope in &mut metrics.scope_metrics {
+            for metric in &mut scope.metrics {
+                let mut total = |attributes: &[KeyValue], start: Datetime, point: Total| {
+                    let key = series_key(&scope.scope, &metric.name, attributes);
+                    let series = totals.entry(key).or_insert_with(|| Series {
+                        start_seconds: start.seconds,
+                        start_nanoseconds: start.nanoseconds,
+                        total: None,
+                        generation,
+                    });
+                    series.generation = generation;
+                    match &mut series.total {
+                        Some(total) => total.add(point),
+                        None => series.total = Some(point),
+                    }
+                    (series.start(), series.total.clone().unwrap())
+                };
+                match &mut metric.data {
+                    MetricData::F64Sum(s) | MetricData::U64Sum(s) | MetricData::S64Sum(s)
+                        if matches!(s.temporality, Temporality::Delta) =>
+                    {
+                        let mut start = s.start_time;
+                        for point in &mut s.data_points {
+                            let (series_start, sum) =
+                                total(&point.attributes, s.start_time, Total::Sum(point.value));
+                            if let Total::Sum(value) = sum {
+                                point.value = value;
+                            }
+                            start = earliest(start, series_start);
+                        }
+                        s.start_time = start;
+                        s.temporality = Temporality::Cumulative;
+                    }
+                    MetricData::F64Histogram(h)
+                    | MetricData::U64Histogram(h)
+                    | MetricData::S64Histogram(h)
+                        if matches!(h.temporality, Temporality::Delta) =>
+                    {
+                        let mut start = h.start_time;
+                        for point in &mut h.data_points {
+                            let delta = Total::Histogram {
+                                count: point.count,
+                                sum: point.sum,
+                                bounds: point.bounds.clone(),
+                                bucket_counts: point.bucket_counts.clone(),
+                                min: point.min,
+                                max: point.max,
+                            };
+                            let (series_start, histogram) =
+                                total(&point.attributes, h.start_time, delta);
+                            if let Total::Histogram {
+                                count,
+                                sum,
+                                bucket_counts,
+                                min,
+                                max,
+                                ..
+                            } = histogram
+                            {
+                                point.count = count;
+                                point.sum = sum;
+                                point.bucket_counts = bucket_counts;
+                                point.min = min;
+                                point.max = max;
+                            }
+                            start = earliest(start, series_start);
+                        }
+                        h.start_time = start;
+                        h.temporality = Temporality::Cumulative;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if totals.len() > self.max_series {
+            let evicted = totals.len() - self.max_series;
+            let mut series: Vec<_> = totals.into_iter().collect();
+            series.sort_by_key(|(_, series)| std::cmp::Reverse(series.generation));
+            series.truncate(self.max_series);
+            totals = series.into_iter().collect();
+            otel_warn!(name: "metric_state_series_evicted", msg = format!("Evicted {} series updated least recently from the metric state", evicted));
+        }
+        if let Err(e) = self.save(generation, totals) {
+            otel_error!(name: "metric_state_save_failed", msg = format!("Operation failed due to an internal error: {}", e));
+        }
+    }
+
+    /// Loads the totals along with the number of the last export that updated them.
+    fn load(&self) -> Result<(u64, HashMap<SeriesKey, Series>), String> {
+        let Some(bytes) = self.store.get(&self.key)? else {
+            return Ok((0, HashMap::new()));
+        };
+        let state: PersistedState = serde_json::from_slice(&bytes).map_err(|e| e.to_string())?;
+        let totals = state
+            .series
+            .into_iter()
+            .map(|s| ((s.scope, s.scope_version, s.metric, s.attributes), s.series))
+            .collect();
+        Ok((state.generation, totals))
+    }
+
+    fn save(&self, generation: u64, totals: HashMap<SeriesKey, Series>) -> Result<(), String> {
+        let state = PersistedState {
+            generation,
+            series: totals
+                .into_iter()
+                .map(
+                    |((scope, scope_version, metric, attributes), series)| PersistedSeries {
+                        scope,
+                        scope_version,
+                        metric,
+                        attributes,
+                        series,
+                    },
+                )
+                .collect(),
+        };
+        let bytes = serde_json::to_vec(&state).map_err(|e| e.to_string())?;
+        self.store.set(&self.key, &bytes)
+    }
+}
+
+fn is_delta(data: &MetricData) -> bool {
+    match data {
+        MetricData::F64Sum(s) | MetricData::U64Sum(s) | MetricData::S64Sum(s) => {
+            matches!(s.temporality, Temporality::Delta)
+        }
+        MetricData::F64Histogram(h) | MetricData::U64Histogram(h) | MetricData::S64Histogram(h) => {
+            matches!(h.temporality, Temporality::Delta)
+        }
+        _ => false,
+    }
+}
+
+fn earliest(a: Datetime, b: Datetime) -> Datetime {
+    if (b.seconds, b.nanoseconds) < (a.seconds, a.nanoseconds) {
+        b
+    } else {
+        a
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PersistedState {
+    /// The number of exports that updated the state.
+    #[serde(default)]
+    generation: u64,
+    series: Vec<PersistedSeries>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PersistedSeries {
+    scope: String,
+    scope_version: Option<String>,
+    metric: String,
+    attributes: Vec<(String, String)>,
+    #[serde(flatten)]
+    series: Series,
+}
+
+/// The running total of a series and when it started.
+#[derive(Debug, Serialize, Deserialize)]
+struct Series {
+    start_seconds: u64,
+    start_nanoseconds: u32,
+    total: Option<Total>,
+    /// The export that last updated the series.
+    #[serde(default)]
+    generation: u64,
+}
+
+impl Series {
+    fn start(&self) -> Datetime {
+        Datetime {
+            seconds: self.start_seconds,
+            nanoseconds: self.start_nanoseconds,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "MetricNumber")]
+enum MetricNumberDef {
+    F64(f64),
+    S64(i64),
+    U64(u64),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Total {
+    Sum(#[serde(with = "MetricNumberDef")] MetricNumber),
+    Histogram {
+        count: u64,
+        #[serde(with = "MetricNumberDef")]
+        sum: MetricNumber,
+        bounds: Vec<f64>,
+        bucket_counts: Vec<u64>,
+        #[serde(with = "option_number")]
+        min: Option<MetricNumber>,
+        #[serde(with = "option_number")]
+        max: Option<MetricNumber>,
+    },
+}
+
+impl Total {
+    /// Adds a delta to the total. If they can't be added, e.g. because the histogram boundaries
+    /// changed, the series restarts from the delta.
+    fn add(&mut self, delta: Total) {
+        let added = match (&mut *self, &delta) {
+            (Total::Sum(total), Total::Sum(sum)) => add_numbers(sum, total),
+            (
+                Total::Histogram {
+                    count,
+                    sum,
+                    bounds,
+                    bucket_counts,
+                    min,
+                    max,
+                },
+                Total::Histogram {
+                    count: delta_count,
+                    sum: delta_sum,
+                    bounds: delta_bounds,
+                    bucket_counts: delta_bucket_counts,
+                    min: delta_min,
+                    max: delta_max,
+                },
+            ) => {
+                let added = bounds == delta_bounds && add_numbers(delta_sum, sum);
+                if added {
+                    *count += delta_count;
+                    for (total, delta) in bucket_counts.iter_mut().zip(delta_bucket_counts) {
+                        *total += delta;
+                    }
+                    merge_min_max(*delta_min, *delta_max, min, max);
+                }
+                added
+            }
+            _ => false,
+        };
+        if !added {
+            *self = delta;
+        }
+    }
+}
+
+mod option_number {
+    use super::{MetricNumber, MetricNumberDef};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct Wrapper(#[serde(with = "MetricNumberDef")] MetricNumber);
+
+    pub(super) fn serialize<S: Serializer>(
+        value: &Option<MetricNumber>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.map(Wrapper).serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<MetricNumber>, D::Error> {
+        Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(n)| n))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{recording_host::take_metrics, WasiMetricExporter};
+    use opentelemetry::metrics::MeterProvider;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct MemoryStore(Arc<Mutex<HashMap<String, Vec<u8>>>>);
+
+    impl MetricStateStore for MemoryStore {
+        fn get(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+            Ok(self.0.lock().unwrap().get(key).cloned())
+        }
+
+        fn set(&self, key: &str, value: &[u8]) -> Result<(), String> {
+            self.0
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), value.to_vec());
+            Ok(())
+        }
+    }
+
+    /// Records with a fresh exporter and provider, as a recycled instance would.
+    fn record_in_new_instance(
+        store: &MemoryStore,
+        max_series: usize,
+        record: impl FnOnce(&opentelemetry::metrics::Meter),
+    ) -> MetricData {
+        let exporter = WasiMetricExporter::builder()
+            .with_state_store(store.clone(), "metrics")
+            .with_max_state_series(max_series)
+            .build();
+        let provider = exporter.meter_provider_builder().build();
+        record(&provider.meter("test"));
+        exporter.export().unwrap();
+        let mut exports = take_metrics();
+        exports
+            .remove(0)
+            .scope_metrics
+            .remove(0)
+            .metrics
+            .remove(0)
+            .data
+    }
+
+    /// Adds `value` to a counter in a new instance.
+    fn count_in_new_instance(store: &MemoryStore, value: u64, route: &'static str) -> Sum {
+        let data = record_in_new_instance(store, 1, |meter| {
+            meter
+                .u64_counter("requests")
+                .build()
+                .add(value, &[opentelemetry::KeyValue::new("route", route)])
+        });
+        match data {
+            MetricData::U64Sum(sum) => sum,
+            _ => panic!("expected a u64 sum"),
+        }
+    }
+
+    #[cfg(feature = "otlp")]
+    #[test]
+    fn ignore_state_store_for_otlp_encoded_metrics() {
+        use opentelemetry_sdk::metrics::{reader::MetricReader, InstrumentKind};
+
+        let exporter = WasiMetricExporter::builder()
+            .with_state_store(MemoryStore::default(), "metrics")
+            .with_otlp_encoding()
+            .with_manual_export_only()
+            .build();
+
+        assert_eq!(
+            exporter.temporality(InstrumentKind::Counter),
+            opentelemetry_sdk::metrics::Temporality::Cumulative
+        );
+    }
+
+    #[test]
+    fn counters_accumulate_across_instances() {
+        let store = MemoryStore::default();
+
+        let first = count_in_new_instance(&store, 2, "/");
+        assert!(matches!(first.temporality, Temporality::Cumulative));
+        assert!(matches!(first.data_points[0].value, MetricNumber::U64(2)));
+
+        let second = count_in_new_instance(&store, 3, "/");
+        assert!(matches!(second.temporality, Temporality::Cumulative));
+        assert!(matches!(second.data_points[0].value, MetricNumber::U64(5)));
+        assert_eq!(
+            (second.start_time.seconds, second.start_time.nanoseconds),
+            (first.start_time.seconds, first.start_time.nanoseconds)
+        );
+    }
+
+    #[test]
+    fn histograms_accumulate_across_instances() {
+        let store = MemoryStore::default();
+        let record = |value| {
+            let data = record_in_new_instance(&store, 1, |meter| {
+                meter
+                    .f64_histogram("duration")
+                    .with_boundaries(vec![10.0])
+                    .build()
+                    .record(value, &[])
+            });
+            match data {
+                MetricData::F64Histogram(histogram) => histogram,
+                _ => panic!("expected an f64 histogram"),
+            }
+        };
+
+        record(4.0);
+        let histogram = record(20.0);
+        let point = &histogram.data_points[0];
+        assert!(matches!(histogram.temporality, Temporality::Cumulative));
+        assert_eq!(point.count, 2);
+        assert!(matches!(point.sum, MetricNumber::F64(sum) if sum == 24.0));
+        assert_eq!(point.bucket_counts, vec![1, 1]);
+        assert!(matches!(point.min, Some(MetricNumber::F64(min)) if min == 4.0));
+        assert!(matches!(point.max, Some(MetricNumber::F64(max)) if max == 20.0));
+    }
+
+    #[test]
+    fn evict_series_updated_least_recently() {
+        let store = MemoryStore::default();
+
+        count_in_new_instance(&store, 2, "/a");
+        count_in_new_instance(&store, 3, "/b");
+        let a = count_in_new_instance(&store, 4, "/a");
+        let b = count_in_new_instance(&store, 5, "/b");
+
+        assert!(matches!(a.data_points[0].value, MetricNumber::U64(4)));
+        assert!(matches!(b.data_points[0].value, MetricNumber::U64(5)));
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{
+    metrics::retry::{metric_data_points, DataPoint},
+    wit::wasi::otel::metrics::*,
+};
+use std::{collections::HashMap, sync::Mutex};
+
+/// Identifies a time series by its scope, metric name and sorted attributes.
+pub(super) type SeriesKey = (String, Option<String>, String, Vec<(String, String)>);
+
+/// Changes whenever the value of a cumulative data point does.
+type Fingerprint = [u64; 2];
+
+/// Leaves cumulative series out of exports until their values change.
+#[derive(Debug, Default)]
+pub(crate) struct UnchangedFilter {
+    exported: Mutex<HashMap<SeriesKey, Fingerprint>>,
+}
+
+/// The series included in an export, remembered once the host accepts it.
+pub(crate) struct ExportedSeries(Vec<(SeriesKey, Fingerprint)>);
+
+impl UnchangedFilter {
+    /// Removes cumulative data points that haven't changed since the last successful export, as
+    /// well as any metrics and scopes left without data points.
+    pub(crate) fn filter(&self, metrics: &mut ResourceMetrics) -> ExportedSeries {
+        let exported = self.exported.lock().unwrap();
+        let mut series = Vec::new();
+        for scope in &mut metrics.scope_metrics {
+            for metric in &mut scope.metrics {
+                let mut changed = |point: &dyn DataPoint, fingerprint: Fingerprint| {
+                    let key = series_key(&scope.scope, &metric.name, point.attributes());
+                    let changed = exported.get(&key) != Some(&fingerprint);
+                    if changed {
+                        series.push((key, fingerprint));
+                    }
+                    changed
+                };
+                match &mut metric.data {
+                    MetricData::F64Sum(s) | MetricData::U64Sum(s) | MetricData::S64Sum(s)
+                        if is_cumulative(s.temporality) =>
+                    {
+                        s.data_points
+                            .retain(|p| changed(p, [number_bits(&p.value), 0]));
+                    }
+                    MetricData::F64Histogram(h)
+                    | MetricData::U64Histogram(h)
+                    | MetricData::S64Histogram(h)
+                        if is_cumulative(h.temporality) =>
+                    {
+                        h.data_points
+                            .retain(|p| changed(p, [p.count, number_bits(&p.sum)]));
+                    }
+                    MetricData::F64ExponentialHistogram(h)
+                    | MetricData::U64ExponentialHistogram(h)
+                    | MetricData::S64ExponentialHistogram(h)
+                        if is_cumulative(h.temporality) =>
+                    {
+                        h.data_points
+                            .retain(|p| changed(p, [p.count, number_bits(&p.sum)]));
+                    }
+                    _ => {}
+                }
+            }
+            scope
+                .metrics
+                .retain(|metric| metric_data_points(&metric.data).0 > 0);
+        }
+        metrics
+            .scope_metrics
+            .retain(|scope| !scope.metrics.is_empty());
+        ExportedSeries(series)
+    }
+
+    /// Remembers the values of series the host accepted.
+    pub(crate) fn commit(&self, series: ExportedSeries) {
+        self.exported.lock().unwrap().extend(series.0);
+    }
+}
+
+fn is_cumulative(temporality: Temporality) -> bool {
+    matches!(temporality, Temporality::Cumulative)
+}
+
+pub(super) fn series_key(
+    scope: &InstrumentationScope,
+    metric: &str,
+    attributes: &[KeyValue],
+) -> SeriesKey {
+    let mut attributes: Vec<_> = attributes
+        .iter()
+        .map(|kv| (kv.key.clone(), kv.value.clone()))
+        .collect();
+    attributes.sort();
+    (
+        scope.name.clone(),
+        scope.version.clone(),
+        metric.to_string(),
+        attributes,
+    )
+}
+
+fn number_bits(number: &MetricNumber) -> u64 {
+    match *number {
+        MetricNumber::F64(v) => v.to_bits(),
+        MetricNumber::S64(v) => v as u64,
+        MetricNumber::U64(v) => v,
+    }
+}
@@ -0,0 +1,352 @@
+use opentelemetry::{otel_warn, Key};
+use opentelemetry_sdk::metrics::{Aggregation, Instrument, Stream};
+
+/// A declarative view that customizes the metrics produced by matching instruments.
+///
+/// Views are added to the [`WasiMetricExporter`] builder, so a set of views can be shipped as a
+/// preset and shared across components. Use [`WasiMetricExporter::meter_provider_builder`] to
+/// register them with an `SdkMeterProvider`; the exporter warns on its first export if it was
+/// registered with a provider some other way, since the views are not applied then.
+///
+/// # Example
+/// ```ignore
+/// let exporter = WasiMetricExporter::builder()
+///     .with_view(
+///         WasiMetricView::builder("http.server.request.duration")
+///             .with_name("http.duration")
+///             .with_allowed_attribute_keys(["http.route"])
+///             .with_histogram_boundaries(vec![0.005, 0.05, 0.5, 5.0])
+///             .build(),
+///     )
+///     .with_view(WasiMetricView::builder("debug.*").with_aggregation(Aggregation::Drop).build())
+///     .build();
+/// let provider = exporter.meter_provider_builder().build();
+/// ```
+///
+/// [`WasiMetricExporter`]: crate::WasiMetricExporter
+/// [`WasiMetricExporter::meter_provider_builder`]: crate::WasiMetricExporter::meter_provider_builder
+#[derive(Debug, Clone)]
+pub struct WasiMetricView {
+    instrument_name: String,
+    is_wildcard: bool,
+    name: Option<String>,
+    description: Option<String>,
+    unit: Option<String>,
+    allowed_attribute_keys: Option<Vec<Key>>,
+    aggregation: Option<Aggregation>,
+}
+
+#[derive(Debug)]
+pub struct WasiMetricViewBuilder {
+    view: WasiMetricView,
+    histogram_boundaries: Option<Vec<f64>>,
+    record_min_max: bool,
+}
+
+impl WasiMetricViewBuilder {
+    /// Rename the matching instrument's metric.
+    ///
+    /// Renaming instruments matched by a wildcard would produce conflicting metrics, so the view
+    /// is ignored in that case.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.view.name = Some(name.into());
+        self
+    }
+
+    /// Replace the description of matching metrics.
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.view.description = Some(description.into());
+        self
+    }
+
+    /// Replace the unit of matching metrics.
+    pub fn with_unit(mut self, unit: impl Into<String>) -> Self {
+        self.view.unit = Some(unit.into());
+        self
+    }
+
+    /// Only keep attributes whose key is in `keys`.
+    pub fn with_allowed_attribute_keys<K: Into<Key>>(
+        mut self,
+        keys: impl IntoIterator<Item = K>,
+    ) -> Self {
+        self.view.allowed_attribute_keys = Some(keys.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Aggregate matching instruments with `aggregation`, e.g. `Aggregation::Drop` to drop them.
+    pub fn with_aggregation(mut self, aggregation: Aggregation) -> Self {
+        self.view.aggregation = Some(aggregation);
+        self.histogram_boundaries = None;
+        self
+    }
+
+    /// Aggregate matching instruments into a histogram with the given bucket boundaries.
+    pub fn with_histogram_boundaries(mut self, boundaries: Vec<f64>) -> Self {
+        self.view.aggregation = None;
+        self.histogram_boundaries = Some(boundaries);
+        self
+    }
+
+    /// Set whether histograms aggregated with [`with_histogram_boundaries`] record their minimum
+    /// and maximum. Defaults to `true`.
+    ///
+    /// [`with_histogram_boundaries`]: WasiMetricViewBuilder::with_histogram_boundaries
+    pub fn with_record_min_max(mut self, record_min_max: bool) -> Self {
+        self.record_min_max = record_min_max;
+        self
+    }
+
+    /// Build the view.
+    pub fn build(mut self) -> WasiMetricView {
+        if let Some(boundaries) = self.histogram_boundaries {
+            self.view.aggregation = Some(Aggregation::ExplicitBucketHistogram {
+                boundaries,
+                record_min_max: self.record_min_max,
+            });
+        }
+        self.view
+    }
+}
+
+impl WasiMetricView {
+    /// Create a new builder for configuring a WasiMetricView.
+    ///
+    /// `instrument_name` matches instrument names exactly, except that `*` matches any sequence of
+    /// characters and `?` matches any single character.
+    pub fn builder(instrument_name: &str) -> WasiMetricViewBuilder {
+        WasiMetricViewBuilder {
+            view: WasiMetricView {
+                instrument_name: instrument_name.to_string(),
+                is_wildcard: instrument_name.contains(['*', '?']),
+                name: None,
+                description: None,
+                unit: None,
+                allowed_attribute_keys: None,
+                aggregation: None,
+            },
+            histogram_boundaries: None,
+            record_min_max: true,
+        }
+    }
+
+    /// Returns whether this view drops some of the attributes of the instrument named
+    /// `instrument_name`.
+    pub(crate) fn filters_attributes(&self, instrument_name: &str) -> bool {
+        self.allowed_attribute_keys.is_some()
+            && !(self.name.is_some() && self.is_wildcard)
+            && glob_match(&self.instrument_name, instrument_name)
+    }
+
+    /// Returns the stream for `instrument` if it matches this view.
+    pub(crate) fn stream(&self, instrument: &Instrument) -> Option<Stream> {
+        if !glob_match(&self.instrument_name, instrument.name()) {
+            return None;
+        }
+        let mut builder = Stream::builder();
+        if let Some(name) = &self.name {
+            if self.is_wildcard {
+                otel_warn!(name: "metric_view_wildcard_rename", msg = format!("Ignoring view renaming instruments matched by the wildcard pattern {}", self.instrument_name));
+                return None;
+            }
+            builder = builder.with_name(name.clone());
+        }
+        if let Some(description) = &self.description {
+            builder = builder.with_description(description.clone());
+        }
+        if let Some(unit) = &self.unit {
+            builder = builder.with_unit(unit.clone());
+        }
+        if let Some(keys) = &self.allowed_attribute_keys {
+            builder = builder.with_allowed_attribute_keys(keys.iter().cloned());
+        }
+        if let Some(aggregation) = &self.aggregation {
+            builder = builder.with_aggregation(aggregation.clone());
+        }
+        match builder.build() {
+            Ok(stream) => Some(stream),
+            Err(e) => {
+                otel_warn!(name: "metric_view_invalid", msg = format!("Ignoring invalid view for instrument {}: {}", instrument.name(), e));
+                None
+            }
+        }
+    }
+}
+
+/// Returns whether `name` matches `pattern`, where `*` matches any sequence of characters and `?`
+/// matches any single character.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // The position of the last `*` and of the character it would consume next.
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, consumed)) => {
+                    backtrack = Some((star, consumed + 1));
+                    p = star + 1;
+                    n = consumed + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        recording_host::take_metrics,
+        wit::wasi::otel::metrics::{Metric, MetricData, MetricNumber},
+        WasiMetricExporter,
+    };
+    use opentelemetry::{metrics::MeterProvider, KeyValue};
+    use opentelemetry_sdk::metrics::SdkMeterProvider;
+
+    fn export(views: Vec<WasiMetricView>) -> Vec<Metric> {
+        let exporter = WasiMetricExporter::builder()
+            .with_manual_export_only()
+            .with_views(views)
+            .build();
+        let provider = exporter.meter_provider_builder().build();
+        let meter = provider.meter("test");
+        meter
+            .f64_histogram("http.server.request.duration")
+            .build()
+            .record(
+                7.0,
+                &[
+                    KeyValue::new("http.route", "/users"),
+                    KeyValue::new("user.id", "42"),
+                ],
+            );
+        meter.u64_counter("debug.cache.hits").build().add(1, &[]);
+        meter.u64_counter("requests").build().add(1, &[]);
+        exporter.export().unwrap();
+        take_metrics()
+            .pop()
+            .unwrap()
+            .scope_metrics
+            .pop()
+            .unwrap()
+            .metrics
+    }
+
+    #[test]
+    fn views_rename_filter_and_rebucket() {
+        let metrics = export(vec![
+            WasiMetricView::builder("http.server.request.duration")
+                .with_name("http.duration")
+                .with_allowed_attribute_keys(["http.route"])
+                .with_histogram_boundaries(vec![5.0, 10.0])
+                .build(),
+            WasiMetricView::builder("debug.*")
+                .with_aggregation(Aggregation::Drop)
+                .build(),
+        ]);
+
+        let mut names: Vec<_> = metrics.iter().map(|m| m.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, vec!["http.duration", "requests"]);
+        let duration = metrics.iter().find(|m| m.name == "http.duration").unwrap();
+        let MetricData::F64Histogram(histogram) = &duration.data else {
+            panic!("unexpected data {:?}", duration.data);
+        };
+        let data_point = &histogram.data_points[0];
+        assert_eq!(data_point.bounds, vec![5.0, 10.0]);
+        assert_eq!(data_point.bucket_counts, vec![0, 1, 0]);
+        assert!(matches!(data_point.min, Some(MetricNumber::F64(min)) if min == 7.0));
+        assert!(matches!(data_point.max, Some(MetricNumber::F64(max)) if max == 7.0));
+        let keys: Vec<_> = data_point.attributes.iter().map(|kv| &kv.key).collect();
+        assert_eq!(keys, vec!["http.route"]);
+    }
+
+    #[test]
+    fn warn_once_when_views_are_not_registered() {
+        let view = || {
+            WasiMetricView::builder("requests")
+                .with_name("hits")
+                .build()
+        };
+        let exporter = WasiMetricExporter::builder()
+            .with_manual_export_only()
+            .with_view(view())
+            .build();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(exporter.clone())
+            .build();
+        provider
+            .meter("test")
+            .u64_counter("requests")
+            .build()
+            .add(1, &[]);
+
+        assert!(exporter.has_unregistered_views());
+        exporter.export().unwrap();
+        assert!(!exporter.has_unregistered_views());
+        assert_eq!(
+            take_metrics()[0].scope_metrics[0].metrics[0].name,
+            "requests"
+        );
+
+        let exporter = WasiMetricExporter::builder()
+            .with_manual_export_only()
+            .with_view(view())
+            .build();
+        let _provider = exporter.meter_provider_builder().build();
+        assert!(!exporter.has_unregistered_views());
+    }
+
+    #[test]
+    fn skip_min_max_when_disabled() {
+        let metrics = export(vec![WasiMetricView::builder(
+            "http.server.request.duration",
+        )
+        .with_histogram_boundaries(vec![5.0, 10.0])
+        .with_record_min_max(false)
+        .build()]);
+
+        let duration = metrics
+            .iter()
+            .find(|m| m.name == "http.server.request.duration")
+            .unwrap();
+        let MetricData::F64Histogram(histogram) = &duration.data else {
+            panic!("unexpected data {:?}", duration.data);
+        };
+        assert_eq!(histogram.data_points[0].bounds, vec![5.0, 10.0]);
+        assert!(histogram.data_points[0].min.is_none());
+        assert!(histogram.data_points[0].max.is_none());
+    }
+
+    #[test]
+    fn wildcard_rename_is_ignored() {
+        let metrics = export(vec![WasiMetricView::builder("*")
+            .with_name("everything")
+            .build()]);
+        assert_eq!(metrics.len(), 3);
+        assert!(metrics.iter().all(|m| m.name != "everything"));
+    }
+
+    #[test]
+    fn match_instrument_names_against_globs() {
+        assert!(glob_match("http.server.*", "http.server.request.duration"));
+        assert!(glob_match("*.duration", "http.server.request.duration"));
+        assert!(glob_match("db.?ool.*", "db.pool.usage"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("http.server", "http-server"));
+        assert!(!glob_match("db.?", "db.pool"));
+        assert!(!glob_match("*.duration", "http.server.duration.max"));
+    }
+}
@@ -0,0 +1,26 @@
+use opentelemetry::{logs::AnyValue, Key, KeyValue};
+
+/// Stands in for the `Redactor` when the `redaction` feature is disabled.
+///
+/// It has no values, so the conversions that take an `Option<&Redactor>` are only ever passed
+/// `None` and don't need to be compiled differently.
+#[derive(Debug, Clone)]
+pub(crate) enum Redactor {}
+
+impl Redactor {
+    pub(crate) fn redact_key_value(&self, _kv: &KeyValue) -> Option<KeyValue> {
+        match *self {}
+    }
+
+    pub(crate) fn redact_log_attribute(&self, _key: &Key, _value: &AnyValue) -> Option<AnyValue> {
+        match *self {}
+    }
+
+    pub(crate) fn redact_any_value(&self, _value: &AnyValue) -> AnyValue {
+        match *self {}
+    }
+
+    pub(crate) fn redact_span(&self, _span: &mut opentelemetry_sdk::trace::SpanData) {
+        match *self {}
+    }
+}
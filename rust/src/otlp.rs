@@ -0,0 +1,405 @@
+//! Encoding of telemetry as OTLP protobuf messages for the `wasi:otel/otlp` interface.
+
+use crate::{PayloadLimits, Redactor};
+use opentelemetry::{logs::AnyValue as LogsAnyValue, Array, Key, StringValue, Value};
+use opentelemetry_proto::{
+    tonic::{
+        collector::{
+            logs::v1::ExportLogsServiceRequest, metrics::v1::ExportMetricsServiceRequest,
+            trace::v1::ExportTraceServiceRequest,
+        },
+        common::v1::{any_value, AnyValue, KeyValue},
+        logs::v1::LogRecord,
+        metrics::v1::{metric::Data, Exemplar},
+    },
+    transform::{
+        common::tonic::ResourceAttributesWithSchema, logs::tonic::group_logs_by_resource_and_scope,
+        trace::tonic::group_spans_by_resource_and_scope,
+    },
+};
+use opentelemetry_sdk::{
+    logs::LogBatch, metrics::data::ResourceMetrics, trace::SpanData, Resource,
+};
+use prost::Message;
+use std::borrow::Cow;
+
+/// Encodes a batch of spans as an `ExportTraceServiceRequest`.
+///
+/// The redactor and limits must already have been applied to the spans.
+pub(crate) fn encode_spans(batch: Vec<SpanData>, resource: Option<&Resource>) -> Vec<u8> {
+    ExportTraceServiceRequest {
+        resource_spans: group_spans_by_resource_and_scope(batch, &to_resource(resource)),
+    }
+    .encode_to_vec()
+}
+
+/// Encodes a batch of log records as an `ExportLogsServiceRequest`, applying the redactor and
+/// limits.
+pub(crate) fn encode_logs(
+    batch: LogBatch<'_>,
+    resource: Option<&Resource>,
+    redactor: Option<&Redactor>,
+    limits: &PayloadLimits,
+) -> Vec<u8> {
+    let mut resource_logs = group_logs_by_resource_and_scope(batch, &to_resource(resource));
+    resource_logs
+        .iter_mut()
+        .flat_map(|resource_logs| &mut resource_logs.scope_logs)
+        .flat_map(|scope_logs| &mut scope_logs.log_records)
+        .for_each(|log| limit_log_record(log, redactor, limits));
+    ExportLogsServiceRequest { resource_logs }.encode_to_vec()
+}
+
+/// Encodes collected metrics as an `ExportMetricsServiceRequest`, applying the redactor to data
+/// point attributes and the redactor and limits to exemplar attributes.
+pub(crate) fn encode_metrics(
+    metrics: &ResourceMetrics,
+    redactor: Option<&Redactor>,
+    limits: &PayloadLimits,
+) -> Vec<u8> {
+    let mut request = ExportMetricsServiceRequest::from(metrics);
+    let metrics = request
+        .resource_metrics
+        .iter_mut()
+        .flat_map(|resource_metrics| &mut resource_metrics.scope_metrics)
+        .flat_map(|scope_metrics| &mut scope_metrics.metrics);
+    for metric in metrics {
+        match &mut metric.data {
+            Some(Data::Gauge(gauge)) => gauge.data_points.iter_mut().for_each(|point| {
+                limit_data_point(
+                    &mut point.attributes,
+                    &mut point.exemplars,
+                    redactor,
+                    limits,
+                )
+            }),
+            Some(Data::Sum(sum)) => sum.data_points.iter_mut().for_each(|point| {
+                limit_data_point(
+                    &mut point.attributes,
+                    &mut point.exemplars,
+                    redactor,
+                    limits,
+                )
+            }),
+            Some(Data::Histogram(histogram)) => {
+                histogram.data_points.iter_mut().for_each(|point| {
+                    limit_data_point(
+                        &mut point.attributes,
+                        &mut point.exemplars,
+                        redactor,
+                        limits,
+                    )
+                })
+            }
+            Some(Data::ExponentialHistogram(histogram)) => {
+                histogram.data_points.iter_mut().for_each(|point| {
+                    limit_data_point(
+                        &mut point.attributes,
+                        &mut point.exemplars,
+                        redactor,
+                        limits,
+                    )
+                })
+            }
+            Some(Data::Summary(summary)) => summary.data_points.iter_mut().for_each(|point| {
+                limit_data_point(&mut point.attributes, &mut [], redactor, limits)
+            }),
+            None => (),
+        }
+    }
+    request.encode_to_vec()
+}
+
+fn to_resource(resource: Option<&Resource>) -> ResourceAttributesWithSchema {
+    resource.map(Into::into).unwrap_or_default()
+}
+
+/// Applies the redactor and limits to an encoded log record, the same way they are applied to
+/// WIT log records, and adds the attributes dropped by the limits to its dropped count.
+fn limit_log_record(log: &mut LogRecord, redactor: Option<&Redactor>, limits: &PayloadLimits) {
+    let attributes = std::mem::take(&mut log.attributes)
+        .into_iter()
+        .filter_map(|kv| {
+            let key = Key::from(kv.key);
+            let value = to_log_value(kv.value?)?;
+            match redactor {
+                Some(redactor) => Some((redactor.redact_log_attribute(&key, &value)?, key)),
+                None => Some((value, key)),
+            }
+        })
+        .enumerate();
+    for (i, (value, key)) in attributes {
+        if !limits.is_attribute_within_limit(i) {
+            log.dropped_attributes_count += 1;
+            continue;
+        }
+        log.attributes.push(KeyValue {
+            key: key.to_string(),
+            value: Some(limits.limit_any_value(&value).into_owned().into()),
+        });
+    }
+    log.body = log.body.take().and_then(to_log_value).map(|body| {
+        let body = match redactor {
+            Some(redactor) => Cow::Owned(redactor.redact_any_value(&body)),
+            None => Cow::Borrowed(&body),
+        };
+        limits.limit_any_value(&body).into_owned().into()
+    });
+}
+
+/// Applies the redactor to the attributes of an encoded data point, and the redactor and limits
+/// to the filtered attributes of its exemplars.
+///
+/// Data point attributes are not limited, as they identify the series.
+fn limit_data_point(
+    attributes: &mut Vec<KeyValue>,
+    exemplars: &mut [Exemplar],
+    redactor: Option<&Redactor>,
+    limits: &PayloadLimits,
+) {
+    if let Some(redactor) = redactor {
+        *attributes = redact_key_values(std::mem::take(attributes), redactor)
+            .iter()
+            .map(Into::into)
+            .collect();
+    }
+    for exemplar in exemplars {
+        let filtered_attributes = std::mem::take(&mut exemplar.filtered_attributes);
+        let mut filtered_attributes = match redactor {
+            Some(redactor) => redact_key_values(filtered_attributes, redactor),
+            None => filtered_attributes
+                .into_iter()
+                .filter_map(to_key_value)
+                .collect(),
+        };
+        limits.limit_key_values(&mut filtered_attributes);
+        exemplar.filtered_attributes = filtered_attributes.iter().map(Into::into).collect();
+    }
+}
+
+fn redact_key_values(
+    attributes: Vec<KeyValue>,
+    redactor: &Redactor,
+) -> Vec<opentelemetry::KeyValue> {
+    attributes
+        .into_iter()
+        .filter_map(to_key_value)
+        .filter_map(|kv| redactor.redact_key_value(&kv))
+        .collect()
+}
+
+/// Converts an encoded log value back into the value it was encoded from.
+fn to_log_value(value: AnyValue) -> Option<LogsAnyValue> {
+    Some(match value.value? {
+        any_value::Value::StringValue(s) => LogsAnyValue::String(s.into()),
+        any_value::Value::BoolValue(b) => LogsAnyValue::Boolean(b),
+        any_value::Value::IntValue(i) => LogsAnyValue::Int(i),
+        any_value::Value::DoubleValue(d) => LogsAnyValue::Double(d),
+        any_value::Value::BytesValue(bytes) => LogsAnyValue::Bytes(Box::new(bytes)),
+        any_value::Value::ArrayValue(array) => LogsAnyValue::ListAny(Box::new(
+            array.values.into_iter().filter_map(to_log_value).collect(),
+        )),
+        any_value::Value::KvlistValue(map) => LogsAnyValue::Map(Box::new(
+            map.values
+                .into_iter()
+                .filter_map(|kv| Some((Key::from(kv.key), to_log_value(kv.value?)?)))
+                .collect(),
+        )),
+    })
+}
+
+/// Converts an encoded attribute back into the attribute it was encoded from.
+///
+/// Metric attributes never hold bytes, maps or mixed arrays, so every attribute encoded from the
+/// SDK converts.
+fn to_key_value(kv: KeyValue) -> Option<opentelemetry::KeyValue> {
+    let value = match kv.value?.value? {
+        any_value::Value::StringValue(s) => Value::String(s.into()),
+        any_value::Value::BoolValue(b) => Value::Bool(b),
+        any_value::Value::IntValue(i) => Value::I64(i),
+        any_value::Value::DoubleValue(d) => Value::F64(d),
+        any_value::Value::ArrayValue(array) => Value::Array(to_array(array.values)?),
+        any_value::Value::BytesValue(_) | any_value::Value::KvlistValue(_) => return None,
+    };
+    Some(opentelemetry::KeyValue::new(kv.key, value))
+}
+
+fn to_array(values: Vec<AnyValue>) -> Option<Array> {
+    let values = values
+        .into_iter()
+        .map(|value| value.value)
+        .collect::<Option<Vec<_>>>()?;
+    Some(match values.first() {
+        Some(any_value::Value::BoolValue(_)) => Array::Bool(
+            values
+                .into_iter()
+                .map(|value| match value {
+                    any_value::Value::BoolValue(b) => Some(b),
+                    _ => None,
+                })
+                .collect::<Option<_>>()?,
+        ),
+        Some(any_value::Value::IntValue(_)) => Array::I64(
+            values
+                .into_iter()
+                .map(|value| match value {
+                    any_value::Value::IntValue(i) => Some(i),
+                    _ => None,
+                })
+                .collect::<Option<_>>()?,
+        ),
+        Some(any_value::Value::DoubleValue(_)) => Array::F64(
+            values
+                .into_iter()
+                .map(|value| match value {
+                    any_value::Value::DoubleValue(d) => Some(d),
+                    _ => None,
+                })
+                .collect::<Option<_>>()?,
+        ),
+        _ => Array::String(
+            values
+                .into_iter()
+                .map(|value| match value {
+                    any_value::Value::StringValue(s) => Some(StringValue::from(s)),
+                    _ => None,
+                })
+                .collect::<Option<_>>()?,
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "redaction")]
+    use crate::WasiMetricExporter;
+    use opentelemetry::InstrumentationScope;
+    #[cfg(feature = "redaction")]
+    use opentelemetry::{
+        logs::{LogRecord as _, Logger, LoggerProvider},
+        metrics::MeterProvider,
+    };
+    #[cfg(feature = "redaction")]
+    use opentelemetry_sdk::{
+        logs::SdkLoggerProvider,
+        metrics::{reader::MetricReader, SdkMeterProvider},
+    };
+
+    fn string_value(kv: &KeyValue) -> &str {
+        match kv.value.as_ref().and_then(|value| value.value.as_ref()) {
+            Some(any_value::Value::StringValue(s)) => s,
+            value => panic!("expected a string value, got {value:?}"),
+        }
+    }
+
+    #[test]
+    fn decode_encoded_spans() {
+        let span = SpanData {
+            span_context: opentelemetry::trace::SpanContext::empty_context(),
+            parent_span_id: opentelemetry::trace::SpanId::INVALID,
+            parent_span_is_remote: false,
+            span_kind: opentelemetry::trace::SpanKind::Internal,
+            name: "work".into(),
+            start_time: std::time::SystemTime::now(),
+            end_time: std::time::SystemTime::now(),
+            attributes: vec![opentelemetry::KeyValue::new("job", "sync")],
+            dropped_attributes_count: 0,
+            events: Default::default(),
+            links: Default::default(),
+            status: opentelemetry::trace::Status::Unset,
+            instrumentation_scope: InstrumentationScope::builder("checkout").build(),
+        };
+        let resource = Resource::builder_empty().with_service_name("shop").build();
+
+        let encoded = encode_spans(vec![span], Some(&resource));
+        let request = ExportTraceServiceRequest::decode(encoded.as_slice()).unwrap();
+
+        let resource_spans = &request.resource_spans[0];
+        let resource_attributes = &resource_spans.resource.as_ref().unwrap().attributes;
+        assert_eq!(resource_attributes[0].key, "service.name");
+        assert_eq!(string_value(&resource_attributes[0]), "shop");
+        let scope_spans = &resource_spans.scope_spans[0];
+        assert_eq!(scope_spans.scope.as_ref().unwrap().name, "checkout");
+        assert_eq!(scope_spans.spans[0].name, "work");
+        assert_eq!(string_value(&scope_spans.spans[0].attributes[0]), "sync");
+    }
+
+    #[cfg(feature = "redaction")]
+    #[test]
+    fn apply_redactor_and_limits_to_log_records() {
+        let logger = SdkLoggerProvider::builder().build().logger("checkout");
+        let mut record = logger.create_log_record();
+        record.set_observed_timestamp(std::time::SystemTime::now());
+        record.set_body("card 4242".into());
+        record.add_attributes([
+            ("password", "hunter2"),
+            ("user", "alice-in-wonderland"),
+            ("order", "42"),
+        ]);
+        let scope = InstrumentationScope::builder("checkout").build();
+        let redactor = Redactor::builder()
+            .with_denied_keys(["password"])
+            .with_value_mask(regex::Regex::new(r"\d{4}").unwrap(), "****")
+            .build();
+        let limits = PayloadLimits::builder()
+            .with_max_attributes(1)
+            .with_max_attribute_value_length(5)
+            .build();
+
+        let batch = [(&record, &scope)];
+        let encoded = encode_logs(LogBatch::new(&batch), None, Some(&redactor), &limits);
+        let request = ExportLogsServiceRequest::decode(encoded.as_slice()).unwrap();
+
+        let log = &request.resource_logs[0].scope_logs[0].log_records[0];
+        assert_eq!(log.attributes.len(), 1);
+        assert_eq!(log.attributes[0].key, "user");
+        assert_eq!(string_value(&log.attributes[0]), "alice");
+        assert_eq!(log.dropped_attributes_count, 1);
+        assert_eq!(
+            log.body.as_ref().unwrap().value,
+            Some(any_value::Value::StringValue("card ".to_string()))
+        );
+    }
+
+    #[cfg(feature = "redaction")]
+    #[test]
+    fn apply_redactor_to_data_point_attributes() {
+        let reader = WasiMetricExporter::builder()
+            .with_manual_export_only()
+            .build();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .build();
+        provider
+            .meter("checkout")
+            .u64_counter("requests")
+            .build()
+            .add(
+                1,
+                &[
+                    opentelemetry::KeyValue::new("route", "/checkout/cart"),
+                    opentelemetry::KeyValue::new("user", "alice"),
+                ],
+            );
+        let mut metrics = ResourceMetrics::default();
+        reader.collect(&mut metrics).unwrap();
+        let redactor = Redactor::builder().with_denied_keys(["user"]).build();
+        let limits = PayloadLimits::builder()
+            .with_max_attribute_value_length(4)
+            .build();
+
+        let encoded = encode_metrics(&metrics, Some(&redactor), &limits);
+        let request = ExportMetricsServiceRequest::decode(encoded.as_slice()).unwrap();
+
+        let metric = &request.resource_metrics[0].scope_metrics[0].metrics[0];
+        assert_eq!(metric.name, "requests");
+        let Some(Data::Sum(sum)) = &metric.data else {
+            panic!("expected a sum, got {:?}", metric.data);
+        };
+        let attributes = &sum.data_points[0].attributes;
+        assert_eq!(attributes.len(), 1);
+        assert_eq!(attributes[0].key, "route");
+        assert_eq!(string_value(&attributes[0]), "/checkout/cart");
+    }
+}
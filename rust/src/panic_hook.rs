@@ -0,0 +1,233 @@
+use crate::{metrics::WeakWasiMetricExporter, WasiMetricExporter};
+use opentelemetry::{
+    logs::{AnyValue, LogRecord, Logger, LoggerProvider, Severity},
+    trace::{Status, TraceContextExt},
+    Context, InstrumentationScope, KeyValue,
+};
+use opentelemetry_sdk::logs::{SdkLogger, SdkLoggerProvider};
+use std::{
+    backtrace::{Backtrace, BacktraceStatus},
+    panic::PanicHookInfo,
+    time::SystemTime,
+};
+
+/// Installs a panic hook that flushes telemetry to the WASI host before the component unwinds or
+/// aborts.
+///
+/// Components built for `wasm32-wasip2` abort on panic, so spans are never ended and a
+/// [`WasiMetricExporter`] is never dropped. When installed, the hook will:
+///
+/// - Record an `exception` event on the current span, set its status to error and end it. The
+///   ended span reaches the host through the tracer provider's `WasiSpanProcessor`.
+/// - Emit a `FATAL` log record through the logger provider, if one was provided, so that its
+///   processors, including the redactor and limits of a `WasiLogProcessor`, apply to it.
+/// - Export metrics one last time if a [`WasiMetricExporter`] was provided.
+///
+/// The previously installed panic hook is called afterwards.
+///
+/// # Example
+/// ```ignore
+/// let exporter = WasiMetricExporter::default();
+/// let provider = SdkMeterProvider::builder().with_reader(exporter.clone());
+/// WasiPanicHook::builder()
+///     .with_logger_provider(&logger_provider)
+///     .with_metric_exporter(exporter.clone())
+///     .install();
+/// ```
+#[derive(Debug, Default)]
+pub struct WasiPanicHook {
+    logger: Option<SdkLogger>,
+    metric_exporter: Option<WeakWasiMetricExporter>,
+}
+
+#[derive(Default)]
+pub struct WasiPanicHookBuilder {
+    logger: Option<SdkLogger>,
+    metric_exporter: Option<WasiMetricExporter>,
+}
+
+impl WasiPanicHookBuilder {
+    /// Emit the `FATAL` log record on panic through `provider`.
+    pub fn with_logger_provider(mut self, provider: &SdkLoggerProvider) -> Self {
+        self.logger = Some(
+            provider.logger_with_scope(
+                InstrumentationScope::builder(env!("CARGO_PKG_NAME"))
+                    .with_version(env!("CARGO_PKG_VERSION"))
+                    .build(),
+            ),
+        );
+        self
+    }
+
+    /// Export the metrics collected by `exporter` when a panic occurs.
+    ///
+    /// The hook does not keep the exporter alive, so it still exports when dropped as usual.
+    pub fn with_metric_exporter(mut self, exporter: WasiMetricExporter) -> Self {
+        self.metric_exporter = Some(exporter);
+        self
+    }
+
+    /// Build the hook without installing it.
+    pub fn build(self) -> WasiPanicHook {
+        WasiPanicHook {
+            logger: self.logger,
+            metric_exporter: self.metric_exporter.as_ref().map(|e| e.downgrade()),
+        }
+    }
+
+    /// Build the hook and install it as the process-wide panic hook.
+    pub fn install(self) {
+        self.build().install()
+    }
+}
+
+impl WasiPanicHook {
+    /// Create a new builder for configuring a WasiPanicHook.
+    pub fn builder() -> WasiPanicHookBuilder {
+        WasiPanicHookBuilder::default()
+    }
+
+    /// Install this hook as the process-wide panic hook, chaining to the previous one.
+    pub fn install(self) {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            self.report(panic_message(info), panic_stacktrace(info));
+            previous(info);
+        }));
+    }
+
+    /// Records a panic on the current span, emits it as a log record and exports metrics.
+    fn report(&self, message: String, stacktrace: String) {
+        let cx = Context::current();
+        let span = cx.span();
+        if span.is_recording() {
+            span.add_event(
+                "exception",
+                vec![
+                    KeyValue::new("exception.message", message.clone()),
+                    KeyValue::new("exception.stacktrace", stacktrace.clone()),
+                ],
+            );
+            span.set_status(Status::error(message.clone()));
+            span.end();
+        }
+
+        if let Some(logger) = &self.logger {
+            let mut record = logger.create_log_record();
+            record.set_timestamp(SystemTime::now());
+            record.set_severity_text(Severity::Fatal.name());
+            record.set_severity_number(Severity::Fatal);
+            record.set_body(AnyValue::from(message.clone()));
+            record.add_attributes([
+                ("exception.message", message),
+                ("exception.stacktrace", stacktrace),
+            ]);
+            logger.emit(record);
+        }
+
+        if let Some(exporter) = self.metric_exporter.as_ref().and_then(|e| e.upgrade()) {
+            _ = exporter.export();
+        }
+    }
+}
+
+/// Extracts the message from a panic payload.
+fn panic_message(info: &PanicHookInfo<'_>) -> String {
+    if let Some(s) = info.payload().downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = info.payload().downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+/// Captures a backtrace, falling back to the panic location where backtraces are unsupported.
+fn panic_stacktrace(info: &PanicHookInfo<'_>) -> String {
+    let backtrace = Backtrace::capture();
+    match (backtrace.status(), info.location()) {
+        (BacktraceStatus::Captured, _) => backtrace.to_string(),
+        (_, Some(location)) => format!("at {location}"),
+        (_, None) => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "redaction")]
+    use crate::Redactor;
+    use crate::{
+        recording_host::{take_ended_spans, take_logs, take_metrics},
+        WasiLogProcessor, WasiSpanProcessor,
+    };
+    use opentelemetry::{
+        metrics::MeterProvider,
+        trace::{Tracer, TracerProvider},
+    };
+    use opentelemetry_sdk::{metrics::SdkMeterProvider, trace::SdkTracerProvider};
+    #[cfg(feature = "redaction")]
+    use regex::Regex;
+
+    #[test]
+    fn report_panic_through_the_configured_pipeline() {
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_span_processor(WasiSpanProcessor::new())
+            .build();
+        let processor = WasiLogProcessor::builder();
+        #[cfg(feature = "redaction")]
+        let processor = processor.with_redactor(
+            Redactor::builder()
+                .with_denied_keys(["exception.stacktrace"])
+                .with_value_mask(Regex::new(r"\d{4}").unwrap(), "****")
+                .build(),
+        );
+        let logger_provider = SdkLoggerProvider::builder()
+            .with_log_processor(processor.build())
+            .build();
+        let exporter = WasiMetricExporter::default();
+        let meter_provider = SdkMeterProvider::builder()
+            .with_reader(exporter.clone())
+            .build();
+        meter_provider
+            .meter("test")
+            .u64_counter("requests")
+            .build()
+            .add(1, &[]);
+        let hook = WasiPanicHook::builder()
+            .with_logger_provider(&logger_provider)
+            .with_metric_exporter(exporter)
+            .build();
+
+        let span = tracer_provider.tracer("test").start("work");
+        let _guard = Context::current_with_span(span).attach();
+        hook.report("card 4242 declined".to_string(), "at main.rs".to_string());
+
+        let spans = take_ended_spans();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].events[0].name, "exception");
+        let logs = take_logs();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].severity_text.as_deref(), Some("FATAL"));
+        #[cfg(feature = "redaction")]
+        {
+            assert_eq!(logs[0].body.as_deref(), Some("\"card **** declined\""));
+            let attributes = logs[0].attributes.as_ref().unwrap();
+            assert_eq!(attributes.len(), 1);
+            assert_eq!(attributes[0].key, "exception.message");
+        }
+        assert_eq!(
+            logs[0].span_id.as_ref(),
+            Some(&spans[0].span_context.span_id)
+        );
+        assert_eq!(take_metrics().len(), 1);
+    }
+
+    #[test]
+    fn skip_log_record_without_a_logger_provider() {
+        WasiPanicHook::default().report("boom".to_string(), String::new());
+
+        assert!(take_logs().is_empty());
+        assert!(take_ended_spans().is_empty());
+    }
+}
@@ -0,0 +1,187 @@
+//! A stand-in for the WASI host that records what is exported to it.
+//!
+//! The generated imports can't be called outside of a component, so tests swap the host calls
+//! they exercise for these. Exports are recorded per thread to keep tests independent.
+
+#[cfg(feature = "batch-export")]
+use crate::wit::batch_export::wasi::otel::batch_export::{LimitedLogRecord, ResourceSpans};
+#[cfg(feature = "span-start")]
+use crate::wit::span_start::wasi::otel::span_start::StartedSpan;
+use crate::wit::wasi::otel::{
+    logs::LogRecord,
+    metrics::ResourceMetrics,
+    tracing::{SpanContext, SpanData},
+};
+use std::{
+    cell::{Cell, RefCell},
+    time::Instant,
+};
+
+thread_local! {
+    static METRICS: RefCell<Vec<ResourceMetrics>> = const { RefCell::new(Vec::new()) };
+    static ENDED_SPANS: RefCell<Vec<SpanData>> = const { RefCell::new(Vec::new()) };
+    #[cfg(feature = "batch-export")]
+    static SPANS: RefCell<Vec<ResourceSpans>> = const { RefCell::new(Vec::new()) };
+    static LOGS: RefCell<Vec<LogRecord>> = const { RefCell::new(Vec::new()) };
+    #[cfg(feature = "batch-export")]
+    static LOG_BATCHES: RefCell<Vec<Vec<LimitedLogRecord>>> = const { RefCell::new(Vec::new()) };
+    static STARTED_SPANS: RefCell<Vec<SpanContext>> = const { RefCell::new(Vec::new()) };
+    #[cfg(feature = "span-start")]
+    static STARTED_SPAN_DETAILS: RefCell<Vec<StartedSpan>> = const { RefCell::new(Vec::new()) };
+    static START: Instant = Instant::now();
+    static EXPORT_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+    static OUTER_SPAN_CONTEXT: RefCell<Option<SpanContext>> = const { RefCell::new(None) };
+    static OUTER_SPAN_CONTEXT_CALLS: Cell<usize> = const { Cell::new(0) };
+    #[cfg(feature = "otlp")]
+    static OTLP_METRICS: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Reads `wasi:clocks/monotonic-clock.now`.
+pub(crate) fn now() -> u64 {
+    START.with(|start| start.elapsed().as_nanos() as u64)
+}
+
+/// Records an export of `wasi:otel/metrics.export`.
+pub(crate) fn export_metrics(metrics: &ResourceMetrics) -> Result<(), String> {
+    if let Some(error) = EXPORT_ERROR.with_borrow(Clone::clone) {
+        return Err(error);
+    }
+    METRICS.with_borrow_mut(|exports| exports.push(metrics.clone()));
+    Ok(())
+}
+
+/// Records a call to `wasi:otel/tracing.on-end`.
+pub(crate) fn on_end(span: &SpanData) {
+    ENDED_SPANS.with_borrow_mut(|ended| ended.push(span.clone()));
+}
+
+/// Returns and clears the spans ended on this thread.
+pub(crate) fn take_ended_spans() -> Vec<SpanData> {
+    ENDED_SPANS.take()
+}
+
+/// Records a call to `wasi:otel/logs.on-emit`.
+pub(crate) fn on_emit(log: &LogRecord) {
+    LOGS.with_borrow_mut(|emitted| emitted.push(log.clone()));
+}
+
+/// Returns and clears the log records emitted on this thread.
+pub(crate) fn take_logs() -> Vec<LogRecord> {
+    LOGS.take()
+}
+
+/// Records an export of `wasi:otel/batch-export.export-spans`.
+#[cfg(feature = "batch-export")]
+pub(crate) fn export_spans(spans: &ResourceSpans) -> Result<(), String> {
+    if let Some(error) = EXPORT_ERROR.with_borrow(Clone::clone) {
+        return Err(error);
+    }
+    SPANS.with_borrow_mut(|exports| exports.push(spans.clone()));
+    Ok(())
+}
+
+/// Returns and clears the span batches exported on this thread.
+#[cfg(feature = "batch-export")]
+pub(crate) fn take_spans() -> Vec<ResourceSpans> {
+    SPANS.take()
+}
+
+/// Records an export of `wasi:otel/batch-export.export-logs`.
+#[cfg(feature = "batch-export")]
+pub(crate) fn export_logs(logs: &[LimitedLogRecord]) -> Result<(), String> {
+    if let Some(error) = EXPORT_ERROR.with_borrow(Clone::clone) {
+        return Err(error);
+    }
+    LOG_BATCHES.with_borrow_mut(|exports| exports.push(logs.to_vec()));
+    Ok(())
+}
+
+/// Returns and clears the log batches exported on this thread.
+#[cfg(feature = "batch-export")]
+pub(crate) fn take_log_batches() -> Vec<Vec<LimitedLogRecord>> {
+    LOG_BATCHES.take()
+}
+
+/// Records an export of `wasi:otel/async-export.export-spans`.
+#[cfg(feature = "async-export")]
+pub(crate) async fn export_spans_async(spans: ResourceSpans) -> Result<(), String> {
+    export_spans(&spans)
+}
+
+/// Records an export of `wasi:otel/async-export.export-logs`.
+#[cfg(feature = "async-export")]
+pub(crate) async fn export_logs_async(logs: Vec<LimitedLogRecord>) -> Result<(), String> {
+    export_logs(&logs)
+}
+
+/// Records an export of `wasi:otel/async-export.export-metrics`.
+#[cfg(feature = "async-export")]
+pub(crate) async fn export_metrics_async(metrics: ResourceMetrics) -> Result<(), String> {
+    export_metrics(&metrics)
+}
+
+/// Records an export of `wasi:otel/otlp.export-metrics`.
+#[cfg(feature = "otlp")]
+pub(crate) fn export_otlp_metrics(request: &[u8]) -> Result<(), String> {
+    if let Some(error) = EXPORT_ERROR.with_borrow(Clone::clone) {
+        return Err(error);
+    }
+    OTLP_METRICS.with_borrow_mut(|exports| exports.push(request.to_vec()));
+    Ok(())
+}
+
+/// Returns and clears the encoded metric requests exported on this thread.
+#[cfg(feature = "otlp")]
+pub(crate) fn take_otlp_metrics() -> Vec<Vec<u8>> {
+    OTLP_METRICS.take()
+}
+
+/// Records a call to `wasi:otel/tracing.on-start`.
+pub(crate) fn on_start(context: &SpanContext) {
+    STARTED_SPANS.with_borrow_mut(|started| started.push(context.clone()));
+}
+
+/// Returns and clears the span contexts started on this thread.
+pub(crate) fn take_started_spans() -> Vec<SpanContext> {
+    STARTED_SPANS.take()
+}
+
+/// Records a call to `wasi:otel/span-start.on-start`.
+#[cfg(feature = "span-start")]
+pub(crate) fn on_start_with_details(span: &StartedSpan) {
+    STARTED_SPAN_DETAILS.with_borrow_mut(|started| started.push(span.clone()));
+}
+
+/// Returns and clears the span details started on this thread.
+#[cfg(feature = "span-start")]
+pub(crate) fn take_started_span_details() -> Vec<StartedSpan> {
+    STARTED_SPAN_DETAILS.take()
+}
+
+/// Reads `wasi:otel/tracing.outer-span-context`, an invalid span context unless one is set.
+pub(crate) fn outer_span_context() -> SpanContext {
+    OUTER_SPAN_CONTEXT_CALLS.set(OUTER_SPAN_CONTEXT_CALLS.get() + 1);
+    OUTER_SPAN_CONTEXT
+        .with_borrow(Clone::clone)
+        .unwrap_or_else(|| opentelemetry::trace::SpanContext::empty_context().into())
+}
+
+/// Sets the outer span context of the host on this thread.
+pub(crate) fn set_outer_span_context(context: Option<SpanContext>) {
+    OUTER_SPAN_CONTEXT.set(context);
+}
+
+/// Returns and resets the number of times the outer span context was read on this thread.
+pub(crate) fn take_outer_span_context_calls() -> usize {
+    OUTER_SPAN_CONTEXT_CALLS.take()
+}
+
+/// Makes exports on this thread fail with `error` until it is cleared.
+pub(crate) fn set_export_error(error: Option<&str>) {
+    EXPORT_ERROR.set(error.map(str::to_string));
+}
+
+/// Returns and clears the metrics exported on this thread.
+pub(crate) fn take_metrics() -> Vec<ResourceMetrics> {
+    METRICS.take()
+}
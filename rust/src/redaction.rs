@@ -0,0 +1,365 @@
+use hmac::{Hmac, Mac};
+use opentelemetry::{logs::AnyValue, Array, Key, KeyValue, StringValue, Value};
+use regex::Regex;
+use sha2::Sha256;
+use std::{borrow::Cow, collections::HashSet, fmt, sync::Arc};
+
+/// Scrubs sensitive data from telemetry before it is converted to WIT records and sent to the host.
+///
+/// A `Redactor` can be attached to the `WasiSpanProcessor`, `WasiLogProcessor` and
+/// `WasiMetricExporter` builders and is applied to span, event and link attributes, log bodies and
+/// attributes, and metric data point and exemplar attributes. It is only available with the
+/// `redaction` cargo feature enabled.
+///
+/// Rules are applied to every attribute in the following order:
+///
+/// 1. If an allow list is configured, attributes whose key is not on it are removed.
+/// 2. Attributes whose key is on the deny list are removed.
+/// 3. Values of hashed keys are replaced with their hex encoded HMAC-SHA256, keyed with a secret
+///    salt.
+/// 4. The query string and fragment are removed from values of URL keys.
+/// 5. Every value mask is applied to all remaining string values.
+///
+/// Nested log maps are redacted recursively using the same rules, except for the allow list which
+/// only applies to top level attributes.
+///
+/// # Example
+/// ```ignore
+/// let redactor = Redactor::builder()
+///     .with_denied_keys(["http.request.header.authorization"])
+///     .with_hashed_keys(salt, ["user.id"])
+///     .with_url_keys(["url.full"])
+///     .with_value_mask(Regex::new(r"[\w.+-]+@[\w-]+\.[\w.]+")?, "[REDACTED]")
+///     .build();
+/// let processor = WasiSpanProcessor::builder()
+///     .with_redactor(redactor.clone())
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    allowed_keys: Option<HashSet<Key>>,
+    denied_keys: HashSet<Key>,
+    hashed_keys: HashSet<Key>,
+    hash_salt: Salt,
+    url_keys: HashSet<Key>,
+    value_masks: Vec<(Regex, String)>,
+}
+
+/// The secret that hashed values are keyed with, kept out of `Debug` output.
+#[derive(Clone, Default)]
+struct Salt(Arc<[u8]>);
+
+impl fmt::Debug for Salt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Salt(..)")
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RedactorBuilder {
+    redactor: Redactor,
+}
+
+impl RedactorBuilder {
+    /// Only keep attributes whose key is in `keys`.
+    ///
+    /// Calling this more than once extends the allow list.
+    pub fn with_allowed_keys<K: Into<Key>>(mut self, keys: impl IntoIterator<Item = K>) -> Self {
+        self.redactor
+            .allowed_keys
+            .get_or_insert_with(HashSet::new)
+            .extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    /// Remove attributes whose key is in `keys`.
+    pub fn with_denied_keys<K: Into<Key>>(mut self, keys: impl IntoIterator<Item = K>) -> Self {
+        self.redactor
+            .denied_keys
+            .extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    /// Replace the values of attributes whose key is in `keys` with their HMAC-SHA256 keyed with
+    /// `salt`.
+    ///
+    /// This keeps values correlatable across telemetry without sending them in clear text. The
+    /// salt must be kept secret, or low-entropy values such as user IDs and email addresses can be
+    /// recovered by hashing guesses. All hashed keys share one salt, so calling this again
+    /// replaces the salt of keys added earlier.
+    pub fn with_hashed_keys<K: Into<Key>>(
+        mut self,
+        salt: impl AsRef<[u8]>,
+        keys: impl IntoIterator<Item = K>,
+    ) -> Self {
+        self.redactor.hash_salt = Salt(salt.as_ref().into());
+        self.redactor
+            .hashed_keys
+            .extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    /// Strip the query string and fragment from the values of attributes whose key is in `keys`.
+    pub fn with_url_keys<K: Into<Key>>(mut self, keys: impl IntoIterator<Item = K>) -> Self {
+        self.redactor
+            .url_keys
+            .extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    /// Replace every match of `pattern` in string values with `replacement`.
+    ///
+    /// `replacement` supports the capture group syntax of [`Regex::replace_all`].
+    pub fn with_value_mask(mut self, pattern: Regex, replacement: impl Into<String>) -> Self {
+        self.redactor
+            .value_masks
+            .push((pattern, replacement.into()));
+        self
+    }
+
+    /// Build the redactor.
+    pub fn build(self) -> Redactor {
+        self.redactor
+    }
+}
+
+impl Redactor {
+    /// Create a new builder for configuring a Redactor.
+    pub fn builder() -> RedactorBuilder {
+        RedactorBuilder::default()
+    }
+
+    /// Redacts an attribute of a span, event, link, metric data point or exemplar, returning
+    /// `None` if it should be removed.
+    pub(crate) fn redact_key_value(&self, kv: &KeyValue) -> Option<KeyValue> {
+        if !self.is_key_allowed(&kv.key) {
+            return None;
+        }
+        let value = if self.hashed_keys.contains(&kv.key) {
+            Value::String(self.hash(&kv.value.as_str()).into())
+        } else {
+            self.redact_value(&kv.key, &kv.value)
+        };
+        Some(KeyValue::new(kv.key.clone(), value))
+    }
+
+    /// Redacts a log attribute, returning `None` if it should be removed.
+    pub(crate) fn redact_log_attribute(&self, key: &Key, value: &AnyValue) -> Option<AnyValue> {
+        if !self.is_key_allowed(key) {
+            return None;
+        }
+        self.redact_any_value_entry(key, value)
+    }
+
+    /// Redacts a log body.
+    pub(crate) fn redact_any_value(&self, value: &AnyValue) -> AnyValue {
+        match value {
+            AnyValue::String(s) => AnyValue::String(self.mask(s.as_str()).into_owned().into()),
+            AnyValue::ListAny(list) => AnyValue::ListAny(Box::new(
+                list.iter().map(|v| self.redact_any_value(v)).collect(),
+            )),
+            AnyValue::Map(map) => AnyValue::Map(Box::new(
+                map.iter()
+                    .filter_map(|(k, v)| Some((k.clone(), self.redact_any_value_entry(k, v)?)))
+                    .collect(),
+            )),
+            other => other.clone(),
+        }
+    }
+
+    /// Redacts all attributes of a span, including its events and links.
+    pub(crate) fn redact_span(&self, span: &mut opentelemetry_sdk::trace::SpanData) {
+        self.redact_key_values(&mut span.attributes);
+        for event in span.events.events.iter_mut() {
+            self.redact_key_values(&mut event.attributes);
+        }
+        for link in span.links.links.iter_mut() {
+            self.redact_key_values(&mut link.attributes);
+        }
+    }
+
+    fn redact_key_values(&self, attributes: &mut Vec<KeyValue>) {
+        *attributes = attributes
+            .iter()
+            .filter_map(|kv| self.redact_key_value(kv))
+            .collect();
+    }
+
+    fn redact_any_value_entry(&self, key: &Key, value: &AnyValue) -> Option<AnyValue> {
+        if self.denied_keys.contains(key) {
+            return None;
+        }
+        if self.hashed_keys.contains(key) {
+            return Some(AnyValue::String(
+                self.hash(&any_value_to_string(value)).into(),
+            ));
+        }
+        match value {
+            AnyValue::String(s) if self.url_keys.contains(key) => Some(AnyValue::String(
+                self.mask(strip_url_query(s.as_str())).into_owned().into(),
+            )),
+            other => Some(self.redact_any_value(other)),
+        }
+    }
+
+    fn redact_value(&self, key: &Key, value: &Value) -> Value {
+        let redact_str = |s: &StringValue| -> StringValue {
+            let s = if self.url_keys.contains(key) {
+                strip_url_query(s.as_str())
+            } else {
+                s.as_str()
+            };
+            self.mask(s).into_owned().into()
+        };
+        match value {
+            Value::String(s) => Value::String(redact_str(s)),
+            Value::Array(Array::String(list)) => {
+                Value::Array(Array::String(list.iter().map(redact_str).collect()))
+            }
+            other => other.clone(),
+        }
+    }
+
+    fn is_key_allowed(&self, key: &Key) -> bool {
+        self.allowed_keys
+            .as_ref()
+            .is_none_or(|allowed| allowed.contains(key))
+            && !self.denied_keys.contains(key)
+    }
+
+    /// Returns the hex encoded HMAC-SHA256 of `value`, keyed with the salt.
+    fn hash(&self, value: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.hash_salt.0)
+            .expect("HMAC accepts keys of any length");
+        mac.update(value.as_bytes());
+        format!("{:x}", mac.finalize().into_bytes())
+    }
+
+    fn mask<'a>(&self, value: &'a str) -> Cow<'a, str> {
+        let mut value = Cow::Borrowed(value);
+        for (pattern, replacement) in self.value_masks.iter() {
+            if let Cow::Owned(masked) = pattern.replace_all(&value, replacement.as_str()) {
+                value = Cow::Owned(masked);
+            }
+        }
+        value
+    }
+}
+
+/// Removes the query string and fragment from a URL.
+fn strip_url_query(url: &str) -> &str {
+    url.find(['?', '#']).map_or(url, |i| &url[..i])
+}
+
+fn any_value_to_string(value: &AnyValue) -> String {
+    match value {
+        AnyValue::String(s) => s.to_string(),
+        AnyValue::Int(i) => i.to_string(),
+        AnyValue::Double(d) => d.to_string(),
+        AnyValue::Boolean(b) => b.to_string(),
+        other => format!("{other:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn redactor() -> Redactor {
+        Redactor::builder()
+            .with_denied_keys(["secret"])
+            .with_hashed_keys("pepper", ["user.id"])
+            .with_url_keys(["url.full"])
+            .with_value_mask(Regex::new(r"[\w.+-]+@[\w-]+\.\w+").unwrap(), "[email]")
+            .build()
+    }
+
+    #[test]
+    fn redact_span_attributes() {
+        let redactor = redactor();
+
+        assert_eq!(
+            redactor.redact_key_value(&KeyValue::new("secret", "hunter2")),
+            None
+        );
+        assert_eq!(
+            redactor.redact_key_value(&KeyValue::new("user.id", 42)),
+            Some(KeyValue::new(
+                "user.id",
+                "05072a49e7c724c2ac3638d3a54eb8848d39fb952094ee45037176152e15d416"
+            ))
+        );
+        assert_eq!(
+            redactor.redact_key_value(&KeyValue::new(
+                "url.full",
+                "https://example.com/login?token=abc#frag"
+            )),
+            Some(KeyValue::new("url.full", "https://example.com/login"))
+        );
+        assert_eq!(
+            redactor.redact_key_value(&KeyValue::new("message", "contact a.b@example.com now")),
+            Some(KeyValue::new("message", "contact [email] now"))
+        );
+        assert_eq!(
+            redactor.redact_key_value(&KeyValue::new("count", 3)),
+            Some(KeyValue::new("count", 3))
+        );
+    }
+
+    #[test]
+    fn hashes_depend_on_the_salt() {
+        let hash = |salt| {
+            Redactor::builder()
+                .with_hashed_keys(salt, ["user.id"])
+                .build()
+                .redact_key_value(&KeyValue::new("user.id", 42))
+        };
+
+        assert_ne!(hash("pepper"), hash("salt"));
+        assert_eq!(hash("pepper"), hash("pepper"));
+        assert!(!format!("{:?}", redactor()).contains("pepper"));
+    }
+
+    #[test]
+    fn redact_allowed_keys() {
+        let redactor = Redactor::builder()
+            .with_allowed_keys(["http.route"])
+            .build();
+
+        assert_eq!(
+            redactor.redact_key_value(&KeyValue::new("http.route", "/users")),
+            Some(KeyValue::new("http.route", "/users"))
+        );
+        assert_eq!(
+            redactor.redact_key_value(&KeyValue::new("user.email", "a@example.com")),
+            None
+        );
+    }
+
+    #[test]
+    fn redact_nested_log_body() {
+        let redactor = redactor();
+        let mut nested: Box<HashMap<Key, AnyValue>> = Box::default();
+        nested.insert(Key::new("secret"), AnyValue::String("hunter2".into()));
+        nested.insert(
+            Key::new("note"),
+            AnyValue::ListAny(Box::new(vec![AnyValue::String("x@example.com".into())])),
+        );
+        let mut body: Box<HashMap<Key, AnyValue>> = Box::default();
+        body.insert(Key::new("nested"), AnyValue::Map(nested));
+
+        let mut expected_nested: Box<HashMap<Key, AnyValue>> = Box::default();
+        expected_nested.insert(
+            Key::new("note"),
+            AnyValue::ListAny(Box::new(vec![AnyValue::String("[email]".into())])),
+        );
+        let mut expected: Box<HashMap<Key, AnyValue>> = Box::default();
+        expected.insert(Key::new("nested"), AnyValue::Map(expected_nested));
+
+        assert_eq!(
+            redactor.redact_any_value(&AnyValue::Map(body)),
+            AnyValue::Map(expected)
+        );
+    }
+}
@@ -1,7 +1,31 @@
+#[cfg(all(
+    feature = "async-export",
+    any(test, all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+mod async_exporter;
 mod conversion;
+mod exporter;
 mod processor;
 mod propagation;
+mod span_metrics;
+mod trace_state;
 
+#[cfg(all(
+    feature = "async-export",
+    any(test, all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+pub use async_exporter::WasiAsyncSpanExporter;
+#[cfg(all(
+    feature = "async-export",
+    any(test, all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+pub use async_exporter::WasiAsyncSpanExporterBuilder;
+pub use exporter::WasiSpanExporter;
+pub use exporter::WasiSpanExporterBuilder;
 pub use processor::WasiSpanProcessor;
+pub use processor::WasiSpanProcessorBuilder;
+pub use propagation::SpanContextError;
 pub use propagation::TraceContextPropagator;
 pub use propagation::WasiPropagator;
+pub use span_metrics::SpanMetricsProcessor;
+pub use span_metrics::SpanMetricsProcessorBuilder;
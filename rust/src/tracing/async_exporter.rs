@@ -0,0 +1,218 @@
+#[cfg(test)]
+use crate::recording_host::export_spans_async as export_spans;
+#[cfg(not(test))]
+use crate::wit::async_export::wasi::otel::async_export::export_spans;
+use crate::{
+    async_support::AssertSend,
+    health::{ExportTracker, Signal},
+    tracing::conversion::to_wasi_resource_spans,
+    ExportHealth, ExportHealthHandle, PayloadLimits, Redactor,
+};
+use opentelemetry::otel_error;
+use opentelemetry_sdk::{
+    error::{OTelSdkError, OTelSdkResult},
+    trace::{SpanData, SpanExporter},
+};
+use std::{
+    future::Future,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// A span exporter that sends batches of spans to a WASI host without blocking.
+///
+/// This uses the asynchronous `wasi:otel/async-export` interface and is meant for wasip3
+/// components running on an async executor, where it can be used with batching span processors.
+/// Empty batches are not sent to the host.
+///
+/// # Example
+/// ```ignore
+/// let exporter = WasiAsyncSpanExporter::builder()
+///     .with_redactor(redactor)
+///     .build();
+/// ```
+#[derive(Debug)]
+pub struct WasiAsyncSpanExporter {
+    is_shutdown: AtomicBool,
+    resource: Option<opentelemetry_sdk::Resource>,
+    tracker: ExportTracker,
+    redactor: Option<Redactor>,
+    limits: PayloadLimits,
+}
+
+#[derive(Default)]
+pub struct WasiAsyncSpanExporterBuilder {
+    redactor: Option<Redactor>,
+    limits: PayloadLimits,
+    health: ExportHealthHandle,
+}
+
+impl WasiAsyncSpanExporterBuilder {
+    /// Redact span, event and link attributes before they are sent to the host.
+    #[cfg(feature = "redaction")]
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+
+    /// Truncate span attributes, events and links that exceed `limits`.
+    pub fn with_limits(mut self, limits: PayloadLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Returns a handle for reading the health of the exporter once it is built, even after it is
+    /// moved into a provider or processor.
+    pub fn health_handle(&self) -> ExportHealthHandle {
+        self.health.clone()
+    }
+
+    /// Build the exporter.
+    pub fn build(self) -> WasiAsyncSpanExporter {
+        WasiAsyncSpanExporter {
+            is_shutdown: AtomicBool::new(false),
+            resource: None,
+            tracker: ExportTracker::new(Signal::Spans, self.health),
+            redactor: self.redactor,
+            limits: self.limits,
+        }
+    }
+}
+
+impl WasiAsyncSpanExporter {
+    /// Create a new `WasiAsyncSpanExporter`.
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    /// Create a new builder for configuring a WasiAsyncSpanExporter.
+    pub fn builder() -> WasiAsyncSpanExporterBuilder {
+        WasiAsyncSpanExporterBuilder::default()
+    }
+
+    /// Returns how exports to the host have fared so far.
+    pub fn health(&self) -> ExportHealth {
+        self.tracker.health()
+    }
+}
+
+impl Default for WasiAsyncSpanExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpanExporter for WasiAsyncSpanExporter {
+    fn export(&self, batch: Vec<SpanData>) -> impl Future<Output = OTelSdkResult> + Send {
+        let spans = (!self.is_shutdown.load(Ordering::Relaxed)).then(|| {
+            to_wasi_resource_spans(
+                batch,
+                self.resource.as_ref(),
+                self.redactor.as_ref(),
+                &self.limits,
+            )
+        });
+        async move {
+            let Some(spans) = spans else {
+                return Err(OTelSdkError::AlreadyShutdown);
+            };
+            if spans.scope_spans.is_empty() {
+                return Ok(());
+            }
+            match AssertSend::new(export_spans(spans)).await {
+                Ok(()) => {
+                    self.tracker.record_success();
+                    Ok(())
+                }
+                Err(e) => {
+                    self.tracker.record_failure(&e);
+                    otel_error!(name: "export_spans_internal_error", msg = format!("Operation failed due to an internal error: {}", e));
+                    Err(OTelSdkError::InternalFailure(e))
+                }
+            }
+        }
+    }
+
+    fn shutdown_with_timeout(&mut self, _timeout: std::time::Duration) -> OTelSdkResult {
+        if self.is_shutdown.swap(true, Ordering::Relaxed) {
+            return Err(OTelSdkError::AlreadyShutdown);
+        }
+        Ok(())
+    }
+
+    fn set_resource(&mut self, resource: &opentelemetry_sdk::Resource) {
+        self.resource = Some(resource.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording_host::{set_export_error, take_spans};
+    use std::{
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    fn export(exporter: &WasiAsyncSpanExporter, name: &'static str) -> OTelSdkResult {
+        let span = SpanData {
+            span_context: opentelemetry::trace::SpanContext::empty_context(),
+            parent_span_id: opentelemetry::trace::SpanId::INVALID,
+            parent_span_is_remote: false,
+            span_kind: opentelemetry::trace::SpanKind::Internal,
+            name: name.into(),
+            start_time: std::time::SystemTime::now(),
+            end_time: std::time::SystemTime::now(),
+            attributes: Vec::new(),
+            dropped_attributes_count: 0,
+            events: Default::default(),
+            links: Default::default(),
+            status: opentelemetry::trace::Status::Unset,
+            instrumentation_scope: Default::default(),
+        };
+        let mut cx = Context::from_waker(Waker::noop());
+        match pin!(exporter.export(vec![span])).poll(&mut cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => panic!("export should complete without waiting"),
+        }
+    }
+
+    #[test]
+    fn exports_spans_and_tracks_health() {
+        let mut exporter = WasiAsyncSpanExporter::new();
+
+        export(&exporter, "work").unwrap();
+        let exports = take_spans();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0].scope_spans[0].spans[0].name, "work");
+
+        set_export_error(Some("host is full"));
+        assert!(export(&exporter, "work").is_err());
+        set_export_error(None);
+        let health = exporter.health();
+        assert_eq!(health.successful_exports(), 1);
+        assert_eq!(health.failed_exports(), 1);
+        assert_eq!(health.last_error(), Some("host is full"));
+
+        exporter.shutdown().unwrap();
+        assert!(matches!(
+            export(&exporter, "work"),
+            Err(OTelSdkError::AlreadyShutdown)
+        ));
+        assert!(take_spans().is_empty());
+    }
+
+    #[test]
+    fn skip_empty_batches() {
+        let exporter = WasiAsyncSpanExporter::new();
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let result = match pin!(exporter.export(Vec::new())).poll(&mut cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => panic!("export should complete without waiting"),
+        };
+
+        assert!(result.is_ok());
+        assert!(take_spans().is_empty());
+        assert_eq!(exporter.health(), ExportHealth::default());
+    }
+}
@@ -1,4 +1,65 @@
-use crate::wit::wasi::otel::tracing::*;
+#[cfg(feature = "batch-export")]
+use crate::wit::batch_export::wasi::otel::batch_export::{ResourceSpans, ScopeSpans};
+use crate::{
+    tracing::{trace_state, SpanContextError},
+    wit::wasi::otel::tracing::*,
+    PayloadLimits, Redactor,
+};
+
+/// Groups a batch of spans by instrumentation scope under `resource`, applying the redactor and
+/// limits to each span.
+#[cfg(feature = "batch-export")]
+pub fn to_wasi_resource_spans(
+    batch: Vec<opentelemetry_sdk::trace::SpanData>,
+    resource: Option<&opentelemetry_sdk::Resource>,
+    redactor: Option<&Redactor>,
+    limits: &PayloadLimits,
+) -> ResourceSpans {
+    let mut scopes: Vec<(opentelemetry::InstrumentationScope, Vec<SpanData>)> = Vec::new();
+    for span in batch {
+        let scope = span.instrumentation_scope.clone();
+        let span = to_wasi_span_data(span, redactor, limits);
+        match scopes.iter_mut().find(|(s, _)| *s == scope) {
+            Some((_, spans)) => spans.push(span),
+            None => scopes.push((scope, vec![span])),
+        }
+    }
+    ResourceSpans {
+        resource: match resource {
+            Some(resource) => resource.into(),
+            None => (&opentelemetry_sdk::Resource::builder_empty().build()).into(),
+        },
+        scope_spans: scopes
+            .into_iter()
+            .map(|(scope, spans)| ScopeSpans {
+                scope: (&scope).into(),
+                spans,
+            })
+            .collect(),
+    }
+}
+
+/// Applies the redactor and limits to a span before converting it.
+pub fn to_wasi_span_data(
+    span: opentelemetry_sdk::trace::SpanData,
+    redactor: Option<&Redactor>,
+    limits: &PayloadLimits,
+) -> SpanData {
+    prepare_span(span, redactor, limits).into()
+}
+
+/// Applies the redactor and limits to a span.
+pub(crate) fn prepare_span(
+    mut span: opentelemetry_sdk::trace::SpanData,
+    redactor: Option<&Redactor>,
+    limits: &PayloadLimits,
+) -> opentelemetry_sdk::trace::SpanData {
+    if let Some(redactor) = redactor {
+        redactor.redact_span(&mut span);
+    }
+    limits.limit_span(&mut span);
+    span
+}
 
 impl From<opentelemetry_sdk::trace::SpanData> for SpanData {
     fn from(value: opentelemetry_sdk::trace::SpanData) -> Self {
@@ -21,47 +82,69 @@ impl From<opentelemetry_sdk::trace::SpanData> for SpanData {
     }
 }
 
+#[cfg(feature = "span-start")]
+impl From<opentelemetry_sdk::trace::SpanData>
+    for crate::wit::span_start::wasi::otel::span_start::StartedSpan
+{
+    fn from(value: opentelemetry_sdk::trace::SpanData) -> Self {
+        Self {
+            span_context: value.span_context.into(),
+            parent_span_id: value.parent_span_id.to_string(),
+            span_kind: value.span_kind.into(),
+            name: value.name.to_string(),
+            start_time: value.start_time.into(),
+            attributes: value.attributes.into_iter().map(|e| (&e).into()).collect(),
+        }
+    }
+}
+
 impl From<opentelemetry::trace::SpanContext> for SpanContext {
     fn from(value: opentelemetry::trace::SpanContext) -> Self {
+        let (trace_state, errors) = trace_state::parse(&value.trace_state().header());
+        trace_state::report(&errors);
         Self {
-            trace_id: format!("{:x}", value.trace_id()),
-            span_id: format!("{:x}", value.span_id()),
+            trace_id: value.trace_id().to_string(),
+            span_id: value.span_id().to_string(),
             trace_flags: value.trace_flags().into(),
             is_remote: value.is_remote(),
-            trace_state: value
-                .trace_state()
-                .header()
-                .split(',')
-                .filter_map(|s| {
-                    if let Some((key, value)) = s.split_once('=') {
-                        Some((key.to_string(), value.to_string()))
-                    } else {
-                        None
-                    }
-                })
-                .collect(),
+            trace_state,
         }
     }
 }
 
-impl From<SpanContext> for opentelemetry::trace::SpanContext {
-    fn from(value: SpanContext) -> Self {
-        let trace_id = opentelemetry::trace::TraceId::from_hex(&value.trace_id)
-            .unwrap_or(opentelemetry::trace::TraceId::INVALID);
-        let span_id = opentelemetry::trace::SpanId::from_hex(&value.span_id)
-            .unwrap_or(opentelemetry::trace::SpanId::INVALID);
-        let trace_state = opentelemetry::trace::TraceState::from_key_value(value.trace_state)
+impl TryFrom<SpanContext> for opentelemetry::trace::SpanContext {
+    type Error = SpanContextError;
+
+    fn try_from(value: SpanContext) -> Result<Self, Self::Error> {
+        let trace_id = parse_id(&value.trace_id, 32)
+            .and_then(|id| opentelemetry::trace::TraceId::from_hex(id).ok())
+            .filter(|id| *id != opentelemetry::trace::TraceId::INVALID)
+            .ok_or_else(|| SpanContextError::InvalidTraceId(value.trace_id.clone()))?;
+        let span_id = parse_id(&value.span_id, 16)
+            .and_then(|id| opentelemetry::trace::SpanId::from_hex(id).ok())
+            .filter(|id| *id != opentelemetry::trace::SpanId::INVALID)
+            .ok_or_else(|| SpanContextError::InvalidSpanId(value.span_id.clone()))?;
+        let (entries, errors) = trace_state::validate(value.trace_state);
+        trace_state::report(&errors);
+        // Every entry has been validated, so this can only fail if the SDK's rules are stricter.
+        let trace_state = opentelemetry::trace::TraceState::from_key_value(entries)
             .unwrap_or_else(|_| opentelemetry::trace::TraceState::default());
-        Self::new(
+        Ok(Self::new(
             trace_id,
             span_id,
             value.trace_flags.into(),
             value.is_remote,
             trace_state,
-        )
+        ))
     }
 }
 
+/// Returns `id` if it is exactly `len` lowercase hexadecimal characters, as required by W3C Trace
+/// Context.
+fn parse_id(id: &str, len: usize) -> Option<&str> {
+    (id.len() == len && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))).then_some(id)
+}
+
 impl From<opentelemetry::trace::TraceFlags> for TraceFlags {
     fn from(value: opentelemetry::trace::TraceFlags) -> Self {
         if value.is_sampled() {
@@ -120,3 +203,147 @@ impl From<opentelemetry::trace::Status> for Status {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span_context(trace_id: &str, span_id: &str) -> SpanContext {
+        SpanContext {
+            trace_id: trace_id.to_string(),
+            span_id: span_id.to_string(),
+            trace_flags: TraceFlags::SAMPLED,
+            is_remote: true,
+            trace_state: vec![("rojo".to_string(), "00f067aa0ba902b7".to_string())],
+        }
+    }
+
+    fn span(scope: &str, name: &'static str) -> opentelemetry_sdk::trace::SpanData {
+        opentelemetry_sdk::trace::SpanData {
+            span_context: opentelemetry::trace::SpanContext::empty_context(),
+            parent_span_id: opentelemetry::trace::SpanId::INVALID,
+            parent_span_is_remote: false,
+            span_kind: opentelemetry::trace::SpanKind::Internal,
+            name: name.into(),
+            start_time: std::time::SystemTime::now(),
+            end_time: std::time::SystemTime::now(),
+            attributes: Vec::new(),
+            dropped_attributes_count: 0,
+            events: Default::default(),
+            links: Default::default(),
+            status: opentelemetry::trace::Status::Unset,
+            instrumentation_scope: opentelemetry::InstrumentationScope::builder(scope.to_string())
+                .build(),
+        }
+    }
+
+    #[test]
+    fn span_data_carries_scope() {
+        let span = to_wasi_span_data(span("http", "a"), None, &PayloadLimits::default());
+        assert_eq!(span.name, "a");
+        assert_eq!(span.instrumentation_scope.name, "http");
+    }
+
+    #[cfg(feature = "batch-export")]
+    #[test]
+    fn group_spans_by_resource_and_scope() {
+        let resource = opentelemetry_sdk::Resource::builder_empty()
+            .with_service_name("checkout")
+            .build();
+        let resource_spans = to_wasi_resource_spans(
+            vec![span("http", "a"), span("db", "b"), span("http", "c")],
+            Some(&resource),
+            None,
+            &PayloadLimits::default(),
+        );
+
+        assert_eq!(
+            resource_spans.resource.attributes[0].key,
+            "service.name".to_string()
+        );
+        let scopes: Vec<_> = resource_spans
+            .scope_spans
+            .iter()
+            .map(|ss| {
+                (
+                    ss.scope.name.as_str(),
+                    ss.spans.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        assert_eq!(scopes, vec![("http", vec!["a", "c"]), ("db", vec!["b"])]);
+        // Spans carry their scope for `on-end`, so it is repeated within `scope-spans`.
+        assert_eq!(
+            resource_spans.scope_spans[1].spans[0]
+                .instrumentation_scope
+                .name,
+            "db"
+        );
+    }
+
+    #[cfg(feature = "span-start")]
+    #[test]
+    fn convert_started_span() {
+        let mut data = span("http", "GET /orders");
+        data.span_kind = opentelemetry::trace::SpanKind::Server;
+        data.parent_span_id = opentelemetry::trace::SpanId::from(0x00f067aa0ba902b7);
+        data.attributes = vec![opentelemetry::KeyValue::new("http.method", "GET")];
+        let started: crate::wit::span_start::wasi::otel::span_start::StartedSpan = data.into();
+
+        assert_eq!(started.name, "GET /orders");
+        assert_eq!(started.span_kind, SpanKind::Server);
+        assert_eq!(started.parent_span_id, "00f067aa0ba902b7");
+        assert_eq!(started.attributes.len(), 1);
+        assert_eq!(started.attributes[0].key, "http.method");
+    }
+
+    #[test]
+    fn ids_are_zero_padded() {
+        let cx: SpanContext = opentelemetry::trace::SpanContext::new(
+            opentelemetry::trace::TraceId::from(0x0af7651916cd43dd8448eb211c80319c),
+            opentelemetry::trace::SpanId::from(0x00f067aa0ba902b7),
+            opentelemetry::trace::TraceFlags::SAMPLED,
+            false,
+            opentelemetry::trace::TraceState::default(),
+        )
+        .into();
+        assert_eq!(cx.trace_id, "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(cx.span_id, "00f067aa0ba902b7");
+    }
+
+    #[test]
+    fn convert_valid_host_span_context() {
+        let cx: opentelemetry::trace::SpanContext =
+            span_context("4bf92f3577b34da6a3ce929d0e0e4736", "00f067aa0ba902b7")
+                .try_into()
+                .unwrap();
+        assert!(cx.is_valid());
+        assert!(cx.is_sampled());
+        assert_eq!(cx.trace_state().get("rojo"), Some("00f067aa0ba902b7"));
+    }
+
+    #[test]
+    fn reject_invalid_host_span_context() {
+        let cases = [
+            ("", "00f067aa0ba902b7"),
+            ("1", "00f067aa0ba902b7"),
+            ("4BF92F3577B34DA6A3CE929D0E0E4736", "00f067aa0ba902b7"),
+            ("00000000000000000000000000000000", "00f067aa0ba902b7"),
+        ];
+        for (trace_id, span_id) in cases {
+            assert_eq!(
+                opentelemetry::trace::SpanContext::try_from(span_context(trace_id, span_id)),
+                Err(SpanContextError::InvalidTraceId(trace_id.to_string()))
+            );
+        }
+        assert_eq!(
+            opentelemetry::trace::SpanContext::try_from(span_context(
+                "4bf92f3577b34da6a3ce929d0e0e4736",
+                "0000000000000000"
+            )),
+            Err(SpanContextError::InvalidSpanId(
+                "0000000000000000".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,331 @@
+#[cfg(all(test, feature = "batch-export"))]
+use crate::recording_host::export_spans;
+#[cfg(all(test, feature = "span-start"))]
+use crate::recording_host::on_start_with_details;
+#[cfg(test)]
+use crate::recording_host::{on_end, on_start};
+#[cfg(any(feature = "otlp", feature = "span-start"))]
+use crate::tracing::conversion::prepare_span;
+#[cfg(feature = "batch-export")]
+use crate::tracing::conversion::to_wasi_resource_spans;
+#[cfg(all(not(test), feature = "batch-export"))]
+use crate::wit::batch_export::wasi::otel::batch_export::export_spans;
+#[cfg(all(not(test), feature = "span-start"))]
+use crate::wit::span_start::wasi::otel::span_start::on_start as on_start_with_details;
+#[cfg(not(test))]
+use crate::wit::wasi::otel::tracing::{on_end, on_start};
+use crate::{
+    health::{ExportTracker, Signal},
+    tracing::conversion::to_wasi_span_data,
+    ExportHealth, ExportHealthHandle, PayloadLimits, Redactor,
+};
+#[cfg(any(feature = "batch-export", feature = "otlp"))]
+use opentelemetry::otel_error;
+use opentelemetry_sdk::{
+    error::{OTelSdkError, OTelSdkResult},
+    trace::{SpanData, SpanExporter},
+};
+use std::{
+    future::Future,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// A span exporter that sends spans to a WASI host.
+///
+/// This allows the WASI transport to be combined with the SDK's span processors or custom
+/// processors, such as filters and enrichers. Spans are sent to the host synchronously, so the
+/// returned future is always ready. Each span is sent through `wasi:otel/tracing.on-end` unless
+/// batch export is enabled, in which case spans are grouped by the resource provided by the
+/// `SdkTracerProvider` the exporter is registered with and by instrumentation scope.
+/// `wasi:otel/tracing.on-end` has no field for the resource, so the host only receives it with
+/// batch export or OTLP encoding enabled.
+///
+/// # Example
+/// ```ignore
+/// let provider = SdkTracerProvider::builder()
+///     .with_span_processor(SimpleSpanProcessor::new(WasiSpanExporter::new()))
+///     .build();
+/// ```
+#[derive(Debug)]
+pub struct WasiSpanExporter {
+    is_shutdown: AtomicBool,
+    resource: Option<opentelemetry_sdk::Resource>,
+    tracker: ExportTracker,
+    redactor: Option<Redactor>,
+    limits: PayloadLimits,
+    #[cfg(feature = "batch-export")]
+    batch_export: bool,
+    #[cfg(feature = "otlp")]
+    otlp_encoding: bool,
+}
+
+#[derive(Default)]
+pub struct WasiSpanExporterBuilder {
+    redactor: Option<Redactor>,
+    limits: PayloadLimits,
+    #[cfg(feature = "batch-export")]
+    batch_export: bool,
+    #[cfg(feature = "otlp")]
+    otlp_encoding: bool,
+    health: ExportHealthHandle,
+}
+
+impl WasiSpanExporterBuilder {
+    /// Redact span, event and link attributes before they are sent to the host.
+    #[cfg(feature = "redaction")]
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+
+    /// Truncate span attributes, events and links that exceed `limits`.
+    pub fn with_limits(mut self, limits: PayloadLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Send each batch of spans to the host in a single call to `wasi:otel/batch-export`, grouped
+    /// by resource and instrumentation scope, instead of calling `wasi:otel/tracing.on-end` for
+    /// every span.
+    ///
+    /// Only enable this if the host implements the `wasi:otel/imports-with-batch-export` world.
+    #[cfg(feature = "batch-export")]
+    pub fn with_batch_export(mut self) -> Self {
+        self.batch_export = true;
+        self
+    }
+
+    /// Send spans to the host as an encoded OTLP `ExportTraceServiceRequest` through the
+    /// `wasi:otel/otlp` interface instead of as WIT records.
+    ///
+    /// The redactor and limits are still applied before encoding.
+    #[cfg(feature = "otlp")]
+    pub fn with_otlp_encoding(mut self) -> Self {
+        self.otlp_encoding = true;
+        self
+    }
+
+    /// Returns a handle for reading the health of the exporter once it is built, even after it is
+    /// moved into a provider or processor.
+    pub fn health_handle(&self) -> ExportHealthHandle {
+        self.health.clone()
+    }
+
+    /// Build the exporter.
+    pub fn build(self) -> WasiSpanExporter {
+        WasiSpanExporter {
+            is_shutdown: AtomicBool::new(false),
+            resource: None,
+            tracker: ExportTracker::new(Signal::Spans, self.health),
+            redactor: self.redactor,
+            limits: self.limits,
+            #[cfg(feature = "batch-export")]
+            batch_export: self.batch_export,
+            #[cfg(feature = "otlp")]
+            otlp_encoding: self.otlp_encoding,
+        }
+    }
+}
+
+impl WasiSpanExporter {
+    /// Create a new `WasiSpanExporter`.
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    /// Create a new builder for configuring a WasiSpanExporter.
+    pub fn builder() -> WasiSpanExporterBuilder {
+        WasiSpanExporterBuilder::default()
+    }
+
+    /// Notifies the host that a span has started.
+    pub(crate) fn start(&self, span_context: &opentelemetry::trace::SpanContext) {
+        if self.is_shutdown.load(Ordering::Relaxed) {
+            return;
+        }
+        on_start(&span_context.clone().into());
+    }
+
+    /// Sends the details of a span that has started to the host.
+    #[cfg(feature = "span-start")]
+    pub(crate) fn start_with_details(&self, span: SpanData) {
+        if self.is_shutdown.load(Ordering::Relaxed) {
+            return;
+        }
+        on_start_with_details(&prepare_span(span, self.redactor.as_ref(), &self.limits).into());
+    }
+
+    /// Sends a batch of ended spans to the host.
+    pub(crate) fn export_batch(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        if self.is_shutdown.load(Ordering::Relaxed) {
+            return Err(OTelSdkError::AlreadyShutdown);
+        }
+        #[cfg(feature = "otlp")]
+        if self.otlp_encoding {
+            let batch = batch
+                .into_iter()
+                .map(|span| prepare_span(span, self.redactor.as_ref(), &self.limits))
+                .collect();
+            let request = crate::otlp::encode_spans(batch, self.resource.as_ref());
+            return self.record(crate::wit::otlp::wasi::otel::otlp::export_traces(&request));
+        }
+        #[cfg(feature = "batch-export")]
+        if self.batch_export {
+            let spans = to_wasi_resource_spans(
+                batch,
+                self.resource.as_ref(),
+                self.redactor.as_ref(),
+                &self.limits,
+            );
+            return self.record(export_spans(&spans));
+        }
+        // `on-end` does not report whether the host accepted the span, so no outcome is recorded.
+        for span in batch {
+            on_end(&to_wasi_span_data(
+                span,
+                self.redactor.as_ref(),
+                &self.limits,
+            ));
+        }
+        Ok(())
+    }
+
+    #[cfg(any(feature = "batch-export", feature = "otlp"))]
+    fn record(&self, result: Result<(), String>) -> OTelSdkResult {
+        match result {
+            Ok(()) => {
+                self.tracker.record_success();
+                Ok(())
+            }
+            Err(e) => {
+                self.tracker.record_failure(&e);
+                otel_error!(name: "export_spans_internal_error", msg = format!("Operation failed due to an internal error: {}", e));
+                Err(OTelSdkError::InternalFailure(e))
+            }
+        }
+    }
+
+    /// Returns how exports to the host have fared so far.
+    pub fn health(&self) -> ExportHealth {
+        self.tracker.health()
+    }
+
+    /// Returns an error if any export failed since the last flush.
+    pub(crate) fn flush(&self) -> OTelSdkResult {
+        if self.is_shutdown.load(Ordering::Relaxed) {
+            return Err(OTelSdkError::AlreadyShutdown);
+        }
+        match self.tracker.take_flush_error() {
+            Some(e) => Err(OTelSdkError::InternalFailure(e)),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn shutdown(&self) -> OTelSdkResult {
+        if self.is_shutdown.swap(true, Ordering::Relaxed) {
+            return Err(OTelSdkError::AlreadyShutdown);
+        }
+        Ok(())
+    }
+}
+
+impl Default for WasiSpanExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpanExporter for WasiSpanExporter {
+    fn export(&self, batch: Vec<SpanData>) -> impl Future<Output = OTelSdkResult> + Send {
+        std::future::ready(self.export_batch(batch))
+    }
+
+    fn shutdown_with_timeout(&mut self, _timeout: std::time::Duration) -> OTelSdkResult {
+        WasiSpanExporter::shutdown(self)
+    }
+
+    fn set_resource(&mut self, resource: &opentelemetry_sdk::Resource) {
+        self.resource = Some(resource.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording_host::take_ended_spans;
+    use std::{
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    fn export(exporter: &WasiSpanExporter, batch: Vec<SpanData>) -> OTelSdkResult {
+        let mut cx = Context::from_waker(Waker::noop());
+        match pin!(exporter.export(batch)).poll(&mut cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => panic!("export should complete without waiting"),
+        }
+    }
+
+    fn span(name: &'static str) -> SpanData {
+        SpanData {
+            span_context: opentelemetry::trace::SpanContext::empty_context(),
+            parent_span_id: opentelemetry::trace::SpanId::INVALID,
+            parent_span_is_remote: false,
+            span_kind: opentelemetry::trace::SpanKind::Internal,
+            name: name.into(),
+            start_time: std::time::SystemTime::now(),
+            end_time: std::time::SystemTime::now(),
+            attributes: vec![opentelemetry::KeyValue::new("password", "hunter2")],
+            dropped_attributes_count: 0,
+            events: Default::default(),
+            links: Default::default(),
+            status: opentelemetry::trace::Status::Unset,
+            instrumentation_scope: opentelemetry::InstrumentationScope::builder("test").build(),
+        }
+    }
+
+    #[test]
+    fn exports_spans_until_shutdown() {
+        let builder = WasiSpanExporter::builder();
+        #[cfg(feature = "redaction")]
+        let builder =
+            builder.with_redactor(Redactor::builder().with_denied_keys(["password"]).build());
+        let mut exporter = builder.build();
+
+        export(&exporter, vec![span("a"), span("b")]).unwrap();
+        let ended = take_ended_spans();
+        assert_eq!(ended.len(), 2);
+        assert_eq!(ended[1].name, "b");
+        #[cfg(feature = "redaction")]
+        assert!(ended[0].attributes.is_empty());
+        assert_eq!(exporter.health(), ExportHealth::default());
+
+        exporter.shutdown_with_timeout(Default::default()).unwrap();
+        assert!(matches!(
+            export(&exporter, vec![span("c")]),
+            Err(OTelSdkError::AlreadyShutdown)
+        ));
+        assert!(take_ended_spans().is_empty());
+        assert!(matches!(
+            exporter.shutdown_with_timeout(Default::default()),
+            Err(OTelSdkError::AlreadyShutdown)
+        ));
+    }
+
+    #[cfg(feature = "batch-export")]
+    #[test]
+    fn report_rejected_batches() {
+        let exporter = WasiSpanExporter::builder().with_batch_export().build();
+
+        crate::recording_host::set_export_error(Some("host is full"));
+        let result = export(&exporter, vec![span("a")]);
+        crate::recording_host::set_export_error(None);
+
+        assert!(matches!(result, Err(OTelSdkError::InternalFailure(_))));
+        assert!(crate::recording_host::take_spans().is_empty());
+        assert_eq!(exporter.health().failed_exports(), 1);
+        assert_eq!(exporter.health().last_error(), Some("host is full"));
+        assert!(exporter.flush().is_err());
+        assert!(exporter.flush().is_ok());
+    }
+}
@@ -1,17 +1,165 @@
-use crate::wit::wasi::otel::tracing::{on_end, on_start};
-use opentelemetry_sdk::{error::OTelSdkResult, trace::SpanProcessor};
-use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "redaction")]
+use crate::Redactor;
+use crate::{
+    ExportHealth, ExportHealthHandle, PayloadLimits, WasiSpanExporter, WasiSpanExporterBuilder,
+};
+use opentelemetry::trace::Span;
+#[cfg(feature = "batch-export")]
+use opentelemetry_sdk::trace::SpanData;
+use opentelemetry_sdk::{
+    error::OTelSdkResult,
+    trace::{SpanExporter, SpanProcessor},
+};
+#[cfg(feature = "batch-export")]
+use std::sync::Mutex;
 
+/// The number of spans buffered for batch export before they are exported without waiting for a
+/// flush.
+#[cfg(feature = "batch-export")]
+const MAX_BATCH_SIZE: usize = 512;
+
+/// A span processor that sends every span to the WASI host as soon as it ends.
+///
+/// This is a thin wrapper around a [`WasiSpanExporter`] that additionally notifies the host when
+/// spans start. With batch export enabled, ended spans are buffered instead and exported together
+/// when the provider is flushed or shut down.
+///
+/// By default, spans are sent through `wasi:otel/tracing.on-end`, whose `span-data` has no field
+/// for the resource, so the host has to attach its own. The resource of the `SdkTracerProvider`
+/// is only sent with batch export or OTLP encoding enabled.
 #[derive(Debug)]
 pub struct WasiSpanProcessor {
-    is_shutdown: AtomicBool,
+    exporter: WasiSpanExporter,
+    notify_on_start: bool,
+    #[cfg(feature = "span-start")]
+    start_details: bool,
+    #[cfg(feature = "batch-export")]
+    batch: Option<Mutex<Vec<SpanData>>>,
+}
+
+pub struct WasiSpanProcessorBuilder {
+    exporter: WasiSpanExporterBuilder,
+    notify_on_start: bool,
+    #[cfg(feature = "span-start")]
+    start_details: bool,
+    #[cfg(feature = "batch-export")]
+    batch_export: bool,
+}
+
+impl Default for WasiSpanProcessorBuilder {
+    fn default() -> Self {
+        Self {
+            exporter: WasiSpanExporter::builder(),
+            notify_on_start: true,
+            #[cfg(feature = "span-start")]
+            start_details: false,
+            #[cfg(feature = "batch-export")]
+            batch_export: false,
+        }
+    }
+}
+
+impl WasiSpanProcessorBuilder {
+    /// Redact span, event and link attributes before they are sent to the host.
+    #[cfg(feature = "redaction")]
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.exporter = self.exporter.with_redactor(redactor);
+        self
+    }
+
+    /// Truncate span attributes, events and links that exceed `limits`.
+    pub fn with_limits(mut self, limits: PayloadLimits) -> Self {
+        self.exporter = self.exporter.with_limits(limits);
+        self
+    }
+
+    /// Configure the processor to NOT notify the host when spans are started.
+    ///
+    /// By default, the processor sends the span context of every span to the host when it starts.
+    /// This method disables those notifications, saving a host call per span.
+    pub fn with_start_notifications_disabled(mut self) -> Self {
+        self.notify_on_start = false;
+        self
+    }
+
+    /// Send the name, kind, parent, start time and initial attributes of spans to the host when
+    /// they start, through `wasi:otel/span-start`, so that in-flight spans can be observed.
+    ///
+    /// This copies the data of every span as it starts, so only enable it if the host uses it.
+    /// The redactor and limits are applied to the attributes.
+    #[cfg(feature = "span-start")]
+    pub fn with_start_details(mut self) -> Self {
+        self.start_details = true;
+        self
+    }
+
+    /// Send spans to the host through `wasi:otel/batch-export` instead of
+    /// `wasi:otel/tracing.on-end`, so that the resource is sent along with them.
+    ///
+    /// Ended spans are buffered and exported in a single call when the provider is flushed or
+    /// shut down, or once 512 spans have accumulated. Call `force_flush` on the provider at the end
+    /// of each request so spans aren't held back.
+    ///
+    /// Only enable this if the host implements the `wasi:otel/imports-with-batch-export` world.
+    #[cfg(feature = "batch-export")]
+    pub fn with_batch_export(mut self) -> Self {
+        self.exporter = self.exporter.with_batch_export();
+        self.batch_export = true;
+        self
+    }
+
+    /// Send spans to the host as an encoded OTLP `ExportTraceServiceRequest` through the
+    /// `wasi:otel/otlp` interface instead of as WIT records.
+    #[cfg(feature = "otlp")]
+    pub fn with_otlp_encoding(mut self) -> Self {
+        self.exporter = self.exporter.with_otlp_encoding();
+        self
+    }
+
+    /// Returns a handle for reading the health of the processor once it is built, even after it
+    /// is moved into a provider.
+    pub fn health_handle(&self) -> ExportHealthHandle {
+        self.exporter.health_handle()
+    }
+
+    /// Build the processor.
+    pub fn build(self) -> WasiSpanProcessor {
+        WasiSpanProcessor {
+            exporter: self.exporter.build(),
+            notify_on_start: self.notify_on_start,
+            #[cfg(feature = "span-start")]
+            start_details: self.start_details,
+            #[cfg(feature = "batch-export")]
+            batch: self.batch_export.then(Mutex::default),
+        }
+    }
 }
 
 impl WasiSpanProcessor {
     /// Create a new `WasiSpanProcessor`.
     pub fn new() -> Self {
-        Self {
-            is_shutdown: AtomicBool::new(false),
+        Self::builder().build()
+    }
+
+    /// Returns how exports to the host have fared so far.
+    pub fn health(&self) -> ExportHealth {
+        self.exporter.health()
+    }
+
+    /// Create a new builder for configuring a WasiSpanProcessor.
+    pub fn builder() -> WasiSpanProcessorBuilder {
+        WasiSpanProcessorBuilder::default()
+    }
+
+    /// Exports the buffered spans, if any.
+    #[cfg(feature = "batch-export")]
+    fn export_buffered(&self) {
+        let Some(batch) = &self.batch else {
+            return;
+        };
+        let spans = std::mem::take(&mut *batch.lock().unwrap());
+        if !spans.is_empty() {
+            _ = self.exporter.export_batch(spans);
         }
     }
 }
@@ -24,37 +172,169 @@ impl Default for WasiSpanProcessor {
 
 impl SpanProcessor for WasiSpanProcessor {
     fn on_start(&self, span: &mut opentelemetry_sdk::trace::Span, _: &opentelemetry::Context) {
-        if self.is_shutdown.load(Ordering::Relaxed) {
+        if !self.notify_on_start {
             return;
         }
-        if let Some(span_data) = span.exported_data() {
-            on_start(&span_data.span_context.into());
+        #[cfg(feature = "span-start")]
+        if self.start_details {
+            if let Some(span_data) = span.exported_data() {
+                self.exporter.start_with_details(span_data);
+            }
+            return;
         }
+        self.exporter.start(span.span_context());
     }
 
     fn on_end(&self, span: opentelemetry_sdk::trace::SpanData) {
-        if self.is_shutdown.load(Ordering::Relaxed) {
+        #[cfg(feature = "batch-export")]
+        if let Some(batch) = &self.batch {
+            let mut spans = batch.lock().unwrap();
+            spans.push(span);
+            if spans.len() >= MAX_BATCH_SIZE {
+                drop(spans);
+                self.export_buffered();
+            }
             return;
         }
-        on_end(&span.into());
+        _ = self.exporter.export_batch(vec![span]);
     }
 
     fn force_flush(&self) -> OTelSdkResult {
-        if self.is_shutdown.load(Ordering::Relaxed) {
-            return OTelSdkResult::Err(opentelemetry_sdk::error::OTelSdkError::AlreadyShutdown);
-        }
-        Ok(())
+        #[cfg(feature = "batch-export")]
+        self.export_buffered();
+        self.exporter.flush()
     }
 
     fn shutdown(&self) -> OTelSdkResult {
         let result = self.force_flush();
-        if self.is_shutdown.swap(true, Ordering::Relaxed) {
-            return OTelSdkResult::Err(opentelemetry_sdk::error::OTelSdkError::AlreadyShutdown);
-        }
+        self.exporter.shutdown()?;
         result
     }
 
     fn shutdown_with_timeout(&self, _timeout: std::time::Duration) -> OTelSdkResult {
         self.shutdown()
     }
+
+    fn set_resource(&mut self, resource: &opentelemetry_sdk::Resource) {
+        self.exporter.set_resource(resource);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording_host::{take_ended_spans, take_started_spans};
+    use opentelemetry::trace::{Tracer, TracerProvider};
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+
+    #[test]
+    fn notify_host_with_span_context_on_start() {
+        let provider = SdkTracerProvider::builder()
+            .with_span_processor(WasiSpanProcessor::new())
+            .build();
+        let span = provider.tracer("test").start("work");
+        let started = take_started_spans();
+
+        assert_eq!(started.len(), 1);
+        assert_eq!(
+            started[0].span_id,
+            span.span_context().span_id().to_string()
+        );
+    }
+
+    #[test]
+    fn export_span_on_end() {
+        let provider = SdkTracerProvider::builder()
+            .with_span_processor(WasiSpanProcessor::new())
+            .build();
+        provider.tracer("test").start("work").end();
+        let ended = take_ended_spans();
+
+        assert_eq!(ended.len(), 1);
+        assert_eq!(ended[0].name, "work");
+    }
+
+    #[cfg(feature = "batch-export")]
+    #[test]
+    fn export_span_in_a_batch_when_enabled() {
+        let provider = SdkTracerProvider::builder()
+            .with_resource(
+                opentelemetry_sdk::Resource::builder_empty()
+                    .with_service_name("checkout")
+                    .build(),
+            )
+            .with_span_processor(WasiSpanProcessor::builder().with_batch_export().build())
+            .build();
+        provider.tracer("test").start("work").end();
+        provider.tracer("test").start("rest").end();
+        assert!(crate::recording_host::take_spans().is_empty());
+        provider.force_flush().unwrap();
+        let exported = crate::recording_host::take_spans();
+
+        assert!(take_ended_spans().is_empty());
+        assert_eq!(exported.len(), 1);
+        assert_eq!(exported[0].resource.attributes[0].key, "service.name");
+        let names: Vec<_> = exported[0].scope_spans[0]
+            .spans
+            .iter()
+            .map(|span| span.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["work", "rest"]);
+    }
+
+    #[cfg(feature = "batch-export")]
+    #[test]
+    fn read_health_once_the_provider_owns_the_processor() {
+        let builder = WasiSpanProcessor::builder().with_batch_export();
+        let health = builder.health_handle();
+        let provider = SdkTracerProvider::builder()
+            .with_span_processor(builder.build())
+            .build();
+
+        provider.tracer("test").start("work").end();
+        provider.force_flush().unwrap();
+        crate::recording_host::set_export_error(Some("host is full"));
+        provider.tracer("test").start("work").end();
+        assert!(provider.force_flush().is_err());
+        crate::recording_host::set_export_error(None);
+        crate::recording_host::take_spans();
+
+        let health = health.health();
+        assert_eq!(health.successful_exports(), 1);
+        assert_eq!(health.failed_exports(), 1);
+        assert_eq!(health.last_error(), Some("host is full"));
+    }
+
+    #[test]
+    fn skip_start_notifications_when_disabled() {
+        let provider = SdkTracerProvider::builder()
+            .with_span_processor(
+                WasiSpanProcessor::builder()
+                    .with_start_notifications_disabled()
+                    .build(),
+            )
+            .build();
+        let _span = provider.tracer("test").start("work");
+
+        assert!(take_started_spans().is_empty());
+    }
+
+    #[cfg(feature = "span-start")]
+    #[test]
+    fn send_span_details_on_start_when_enabled() {
+        let provider = SdkTracerProvider::builder()
+            .with_span_processor(WasiSpanProcessor::builder().with_start_details().build())
+            .build();
+        let _span = provider
+            .tracer("test")
+            .span_builder("work")
+            .with_attributes([opentelemetry::KeyValue::new("job", "sync")])
+            .start(&provider.tracer("test"));
+        let started = crate::recording_host::take_started_span_details();
+
+        assert!(take_started_spans().is_empty());
+        assert_eq!(started.len(), 1);
+        assert_eq!(started[0].name, "work");
+        assert_eq!(started[0].attributes[0].key, "job");
+    }
 }
@@ -1,16 +1,52 @@
+#[cfg(test)]
+use crate::recording_host::outer_span_context;
+#[cfg(not(test))]
 use crate::wit::wasi::otel::tracing::outer_span_context;
-use opentelemetry::{trace::TraceContextExt, Context};
+use opentelemetry::{otel_warn, trace::TraceContextExt, Context};
+use std::fmt;
 
 pub trait WasiPropagator {
     fn extract(&self, cx: &Context) -> Context;
 }
 
+/// An error returned when the span context provided by the host is invalid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpanContextError {
+    /// The trace ID is not 32 lowercase hexadecimal characters or is all zeros.
+    InvalidTraceId(String),
+    /// The span ID is not 16 lowercase hexadecimal characters or is all zeros.
+    InvalidSpanId(String),
+}
+
+impl fmt::Display for SpanContextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidTraceId(id) => write!(f, "invalid trace ID '{id}'"),
+            Self::InvalidSpanId(id) => write!(f, "invalid span ID '{id}'"),
+        }
+    }
+}
+
+impl std::error::Error for SpanContextError {}
+
 pub struct TraceContextPropagator {}
 
 impl TraceContextPropagator {
     pub fn new() -> Self {
         Self {}
     }
+
+    /// Extracts the host's span context into `cx`, returning an error if it is invalid.
+    ///
+    /// A span context whose trace and span IDs are all zeros means the host has no outer span, so
+    /// `cx` is returned unchanged.
+    pub fn try_extract(&self, cx: &Context) -> Result<Context, SpanContextError> {
+        let span_context = outer_span_context();
+        if is_unset(&span_context.trace_id) && is_unset(&span_context.span_id) {
+            return Ok(cx.clone());
+        }
+        Ok(cx.with_remote_span_context(span_context.try_into()?))
+    }
 }
 
 impl Default for TraceContextPropagator {
@@ -20,7 +56,84 @@ impl Default for TraceContextPropagator {
 }
 
 impl WasiPropagator for TraceContextPropagator {
+    /// Extracts the host's span context into `cx`.
+    ///
+    /// If the host has no outer span, `cx` is returned unchanged. If the host's span context is
+    /// malformed, a warning is emitted and `cx` is returned unchanged so that new spans are not
+    /// attached to an invalid parent.
     fn extract(&self, cx: &Context) -> Context {
-        cx.with_remote_span_context(outer_span_context().into())
+        self.try_extract(cx).unwrap_or_else(|e| {
+            otel_warn!(name: "extract_invalid_span_context", msg = format!("Ignoring span context from host: {}", e));
+            cx.clone()
+        })
+    }
+}
+
+/// Returns whether `id` is the all-zero ID the host sends when it has no outer span.
+fn is_unset(id: &str) -> bool {
+    id.bytes().all(|b| b == b'0')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording_host::set_outer_span_context;
+    use crate::wit::wasi::otel::tracing::{SpanContext, TraceFlags};
+
+    fn host_span_context(trace_id: &str, span_id: &str) -> SpanContext {
+        SpanContext {
+            trace_id: trace_id.to_string(),
+            span_id: span_id.to_string(),
+            trace_flags: TraceFlags::SAMPLED,
+            is_remote: true,
+            trace_state: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn extract_the_host_span_context() {
+        set_outer_span_context(Some(host_span_context(
+            "4bf92f3577b34da6a3ce929d0e0e4736",
+            "00f067aa0ba902b7",
+        )));
+        let cx = TraceContextPropagator::new().extract(&Context::new());
+        set_outer_span_context(None);
+
+        let span_context = cx.span().span_context().clone();
+        assert_eq!(
+            span_context.trace_id().to_string(),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+        assert_eq!(span_context.span_id().to_string(), "00f067aa0ba902b7");
+        assert!(span_context.is_remote());
+    }
+
+    #[test]
+    fn leave_context_unchanged_without_a_host_span() {
+        let propagator = TraceContextPropagator::new();
+
+        assert!(!propagator.extract(&Context::new()).has_active_span());
+        let cx = propagator.try_extract(&Context::new()).unwrap();
+        assert!(!cx.has_active_span());
+    }
+
+    #[test]
+    fn reject_malformed_host_span_context() {
+        set_outer_span_context(Some(host_span_context(
+            "4BF92F3577B34DA6A3CE929D0E0E4736",
+            "00f067aa0ba902b7",
+        )));
+        let propagator = TraceContextPropagator::new();
+        let result = propagator.try_extract(&Context::new());
+        let cx = propagator.extract(&Context::new());
+        set_outer_span_context(None);
+
+        assert_eq!(
+            result.err(),
+            Some(SpanContextError::InvalidTraceId(
+                "4BF92F3577B34DA6A3CE929D0E0E4736".to_string()
+            ))
+        );
+        assert!(!cx.has_active_span());
     }
 }
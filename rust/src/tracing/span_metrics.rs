@@ -0,0 +1,237 @@
+use opentelemetry::{
+    metrics::{Counter, Histogram, MeterProvider},
+    trace::{SpanKind, Status},
+    InstrumentationScope, Key, KeyValue,
+};
+use opentelemetry_sdk::{
+    error::OTelSdkResult,
+    trace::{SpanData, SpanProcessor},
+};
+use std::time::Duration;
+
+/// The default duration histogram boundaries, in seconds.
+const DEFAULT_BOUNDARIES: [f64; 14] = [
+    0.005, 0.01, 0.025, 0.05, 0.075, 0.1, 0.25, 0.5, 0.75, 1.0, 2.5, 5.0, 7.5, 10.0,
+];
+
+/// A span processor that derives request rate, error rate and duration (RED) metrics from spans.
+///
+/// Register it with the tracer provider next to a [`WasiSpanProcessor`]. Every span that ends is
+/// counted by `traces.span.metrics.calls` and its duration recorded by
+/// `traces.span.metrics.duration`, in seconds, with the `span.name`, `span.kind` and
+/// `status.code` attributes and any configured dimensions. The names match the OpenTelemetry
+/// Collector's span metrics connector, so existing dashboards can be reused.
+///
+/// # Sampling
+///
+/// Spans dropped by the tracer provider's sampler never reach span processors, so they are not
+/// counted. With any sampler other than `Sampler::AlwaysOn`, the metrics only reflect the sampled
+/// spans and undercount the requests the component handles. Record request metrics directly
+/// when sampling is enabled and exact counts matter.
+///
+/// # Example
+/// ```ignore
+/// let span_metrics = SpanMetricsProcessor::builder(&meter_provider)
+///     .with_dimensions(["http.route"])
+///     .build();
+/// let tracer_provider = SdkTracerProvider::builder()
+///     .with_span_processor(WasiSpanProcessor::new())
+///     .with_span_processor(span_metrics)
+///     .build();
+/// ```
+///
+/// [`WasiSpanProcessor`]: crate::WasiSpanProcessor
+#[derive(Debug)]
+pub struct SpanMetricsProcessor {
+    calls: Counter<u64>,
+    duration: Histogram<f64>,
+    dimensions: Vec<Key>,
+}
+
+pub struct SpanMetricsProcessorBuilder<'a, P> {
+    provider: &'a P,
+    dimensions: Vec<Key>,
+    boundaries: Vec<f64>,
+}
+
+impl<P: MeterProvider> SpanMetricsProcessorBuilder<'_, P> {
+    /// Add span attributes to the metrics' attributes. Spans without an attribute are recorded
+    /// without it.
+    ///
+    /// Every distinct value creates a new series, so only use attributes with few values, such
+    /// as `http.route`.
+    pub fn with_dimensions(mut self, keys: impl IntoIterator<Item = impl Into<Key>>) -> Self {
+        self.dimensions.extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    /// Set the boundaries of the duration histogram, in seconds.
+    pub fn with_histogram_boundaries(mut self, boundaries: Vec<f64>) -> Self {
+        self.boundaries = boundaries;
+        self
+    }
+
+    /// Build the processor, creating its instruments with the meter provider.
+    pub fn build(self) -> SpanMetricsProcessor {
+        let meter = self.provider.meter_with_scope(
+            InstrumentationScope::builder(env!("CARGO_PKG_NAME"))
+                .with_version(env!("CARGO_PKG_VERSION"))
+                .build(),
+        );
+        SpanMetricsProcessor {
+            calls: meter
+                .u64_counter("traces.span.metrics.calls")
+                .with_description("The number of spans that ended.")
+                .with_unit("{call}")
+                .build(),
+            duration: meter
+                .f64_histogram("traces.span.metrics.duration")
+                .with_description("The duration of spans.")
+                .with_unit("s")
+                .with_boundaries(self.boundaries)
+                .build(),
+            dimensions: self.dimensions,
+        }
+    }
+}
+
+impl SpanMetricsProcessor {
+    /// Create a new builder for configuring a SpanMetricsProcessor that records into `provider`.
+    pub fn builder<P: MeterProvider>(provider: &P) -> SpanMetricsProcessorBuilder<'_, P> {
+        SpanMetricsProcessorBuilder {
+            provider,
+            dimensions: Vec::new(),
+            boundaries: DEFAULT_BOUNDARIES.to_vec(),
+        }
+    }
+
+    fn attributes(&self, span: &SpanData) -> Vec<KeyValue> {
+        let mut attributes = vec![
+            KeyValue::new("span.name", span.name.clone()),
+            KeyValue::new("span.kind", span_kind(&span.span_kind)),
+            KeyValue::new("status.code", status_code(&span.status)),
+        ];
+        attributes.extend(
+            self.dimensions
+                .iter()
+                .filter_map(|key| span.attributes.iter().find(|kv| &kv.key == key).cloned()),
+        );
+        attributes
+    }
+}
+
+fn span_kind(kind: &SpanKind) -> &'static str {
+    match kind {
+        SpanKind::Client => "SPAN_KIND_CLIENT",
+        SpanKind::Server => "SPAN_KIND_SERVER",
+        SpanKind::Producer => "SPAN_KIND_PRODUCER",
+        SpanKind::Consumer => "SPAN_KIND_CONSUMER",
+        SpanKind::Internal => "SPAN_KIND_INTERNAL",
+    }
+}
+
+fn status_code(status: &Status) -> &'static str {
+    match status {
+        Status::Unset => "STATUS_CODE_UNSET",
+        Status::Error { .. } => "STATUS_CODE_ERROR",
+        Status::Ok => "STATUS_CODE_OK",
+    }
+}
+
+impl SpanProcessor for SpanMetricsProcessor {
+    fn on_start(&self, _span: &mut opentelemetry_sdk::trace::Span, _cx: &opentelemetry::Context) {}
+
+    fn on_end(&self, span: SpanData) {
+        let attributes = self.attributes(&span);
+        let duration = span
+            .end_time
+            .duration_since(span.start_time)
+            .unwrap_or_default();
+        self.calls.add(1, &attributes);
+        self.duration.record(duration.as_secs_f64(), &attributes);
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+
+    fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        recording_host::take_metrics,
+        wit::wasi::otel::metrics::{MetricData, MetricNumber},
+        WasiMetricExporter,
+    };
+    use opentelemetry::trace::{Span, Tracer, TracerProvider};
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+
+    #[test]
+    fn records_calls_and_duration_per_span() {
+        let exporter = WasiMetricExporter::default();
+        let meter_provider = exporter.meter_provider_builder().build();
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_span_processor(
+                SpanMetricsProcessor::builder(&meter_provider)
+                    .with_dimensions(["http.route"])
+                    .build(),
+            )
+            .build();
+        let tracer = tracer_provider.tracer("test");
+
+        for _ in 0..2 {
+            let mut span = tracer
+                .span_builder("GET /users")
+                .with_kind(SpanKind::Server)
+                .with_attributes([
+                    KeyValue::new("http.route", "/users"),
+                    KeyValue::new("user.id", "42"),
+                ])
+                .start(&tracer);
+            span.end();
+        }
+        let mut span = tracer.start("fetch");
+        span.set_status(Status::error("timed out"));
+        span.end();
+        exporter.export().unwrap();
+
+        let exports = take_metrics();
+        let metrics = &exports[0].scope_metrics[0].metrics;
+        assert_eq!(metrics[0].name, "traces.span.metrics.calls");
+        let MetricData::U64Sum(calls) = &metrics[0].data else {
+            panic!("expected a u64 sum");
+        };
+        let calls: Vec<_> = calls
+            .data_points
+            .iter()
+            .map(|p| {
+                let mut attributes: Vec<_> = p
+                    .attributes
+                    .iter()
+                    .map(|kv| format!("{}={}", kv.key, kv.value))
+                    .collect();
+                attributes.sort();
+                (attributes, p.value)
+            })
+            .collect();
+        assert_eq!(calls.len(), 2);
+        for (attributes, value) in calls {
+            if attributes.iter().any(|a| a.contains("GET /users")) {
+                assert!(attributes.iter().any(|a| a.starts_with("http.route=")));
+                assert!(attributes.iter().any(|a| a.contains("SPAN_KIND_SERVER")));
+                assert!(!attributes.iter().any(|a| a.starts_with("user.id=")));
+                assert!(matches!(value, MetricNumber::U64(2)));
+            } else {
+                assert!(attributes.iter().any(|a| a.contains("STATUS_CODE_ERROR")));
+                assert!(matches!(value, MetricNumber::U64(1)));
+            }
+        }
+        assert_eq!(metrics[1].name, "traces.span.metrics.duration");
+        assert!(matches!(metrics[1].data, MetricData::F64Histogram(_)));
+    }
+}
@@ -0,0 +1,216 @@
+//! Parsing and validation of the W3C `tracestate` header.
+//!
+//! See <https://www.w3.org/TR/trace-context/#tracestate-header>.
+
+use opentelemetry::otel_warn;
+use std::fmt;
+
+/// The maximum number of list members in a `tracestate`.
+const MAX_ENTRIES: usize = 32;
+
+/// Why a `tracestate` list member was discarded.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TraceStateError {
+    /// The list member has no `=` separating the key and value.
+    MissingSeparator(String),
+    /// The key does not match the `simple-key` or `multi-tenant-key` grammar.
+    InvalidKey(String),
+    /// The value does not match the `value` grammar.
+    InvalidValue { key: String, value: String },
+    /// The key has already appeared earlier in the list.
+    DuplicateKey(String),
+    /// The list member is past the 32 entry limit.
+    TooManyEntries { key: String },
+}
+
+impl fmt::Display for TraceStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingSeparator(member) => {
+                write!(f, "list member '{member}' is missing a '=' separator")
+            }
+            Self::InvalidKey(key) => write!(f, "key '{key}' is invalid"),
+            Self::InvalidValue { key, value } => {
+                write!(f, "value '{value}' for key '{key}' is invalid")
+            }
+            Self::DuplicateKey(key) => write!(f, "key '{key}' is duplicated"),
+            Self::TooManyEntries { key } => {
+                write!(f, "key '{key}' exceeds the limit of {MAX_ENTRIES} entries")
+            }
+        }
+    }
+}
+
+/// Parses a `tracestate` header into its ordered list members.
+///
+/// Whitespace around list members is ignored, as are empty list members. Invalid, duplicate and
+/// excess members are discarded and returned as errors.
+pub(crate) fn parse(header: &str) -> (Vec<(String, String)>, Vec<TraceStateError>) {
+    let mut errors = Vec::new();
+    let members = header
+        .split(',')
+        .map(|member| member.trim_matches([' ', '\t']))
+        .filter(|member| !member.is_empty())
+        .filter_map(|member| match member.split_once('=') {
+            Some((key, value)) => Some((key.to_string(), value.to_string())),
+            None => {
+                errors.push(TraceStateError::MissingSeparator(member.to_string()));
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    let (entries, mut validation_errors) = validate(members);
+    errors.append(&mut validation_errors);
+    (entries, errors)
+}
+
+/// Validates an ordered list of `tracestate` members.
+///
+/// The first occurrence of a key wins and only the first 32 valid members are kept. Everything
+/// else is discarded and returned as errors.
+pub(crate) fn validate(
+    members: impl IntoIterator<Item = (String, String)>,
+) -> (Vec<(String, String)>, Vec<TraceStateError>) {
+    let mut entries: Vec<(String, String)> = Vec::new();
+    let mut errors = Vec::new();
+    for (key, value) in members {
+        if !is_valid_key(&key) {
+            errors.push(TraceStateError::InvalidKey(key));
+        } else if !is_valid_value(&value) {
+            errors.push(TraceStateError::InvalidValue { key, value });
+        } else if entries.iter().any(|(k, _)| *k == key) {
+            errors.push(TraceStateError::DuplicateKey(key));
+        } else if entries.len() == MAX_ENTRIES {
+            errors.push(TraceStateError::TooManyEntries { key });
+        } else {
+            entries.push((key, value));
+        }
+    }
+    (entries, errors)
+}
+
+/// Reports discarded list members through OpenTelemetry's internal diagnostics.
+pub(crate) fn report(errors: &[TraceStateError]) {
+    for error in errors {
+        otel_warn!(name: "trace_state_entry_discarded", msg = format!("Discarded trace state entry: {}", error));
+    }
+}
+
+/// `simple-key` or `multi-tenant-key`.
+fn is_valid_key(key: &str) -> bool {
+    match key.split_once('@') {
+        None => is_valid_key_part(key, 256, |b| b.is_ascii_lowercase()),
+        Some((tenant_id, system_id)) => {
+            is_valid_key_part(tenant_id, 241, |b| {
+                b.is_ascii_lowercase() || b.is_ascii_digit()
+            }) && is_valid_key_part(system_id, 14, |b| b.is_ascii_lowercase())
+        }
+    }
+}
+
+fn is_valid_key_part(part: &str, max_len: usize, is_valid_first: impl Fn(u8) -> bool) -> bool {
+    let bytes = part.as_bytes();
+    !bytes.is_empty()
+        && bytes.len() <= max_len
+        && is_valid_first(bytes[0])
+        && bytes[1..].iter().all(|&b| {
+            b.is_ascii_lowercase() || b.is_ascii_digit() || matches!(b, b'_' | b'-' | b'*' | b'/')
+        })
+}
+
+/// Up to 256 printable ASCII characters excluding `,` and `=`, not ending in a space.
+fn is_valid_value(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    !bytes.is_empty()
+        && bytes.len() <= 256
+        && bytes[bytes.len() - 1] != b' '
+        && bytes
+            .iter()
+            .all(|&b| (0x20..=0x7e).contains(&b) && b != b',' && b != b'=')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_preserves_order_and_trims_whitespace() {
+        let (entries, errors) =
+            parse(" rojo=00f067aa0ba902b7 ,\tcongo=t61rcWkgMzE,, tenant@vendor=a b");
+        assert_eq!(
+            entries,
+            pairs(&[
+                ("rojo", "00f067aa0ba902b7"),
+                ("congo", "t61rcWkgMzE"),
+                ("tenant@vendor", "a b"),
+            ])
+        );
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn parse_reports_invalid_entries() {
+        let (entries, errors) =
+            parse("rojo=1,Upper=2,novalue,bad@Vendor=3,congo=a=b,rojo=4,empty=,ok=5");
+        assert_eq!(entries, pairs(&[("rojo", "1"), ("ok", "5")]));
+        assert_eq!(
+            errors,
+            vec![
+                TraceStateError::MissingSeparator("novalue".into()),
+                TraceStateError::InvalidKey("Upper".into()),
+                TraceStateError::InvalidKey("bad@Vendor".into()),
+                TraceStateError::InvalidValue {
+                    key: "congo".into(),
+                    value: "a=b".into()
+                },
+                TraceStateError::DuplicateKey("rojo".into()),
+                TraceStateError::InvalidValue {
+                    key: "empty".into(),
+                    value: "".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_enforces_entry_limit() {
+        let members = (0..40).map(|i| (format!("k{i}"), i.to_string()));
+        let (entries, errors) = validate(members);
+        assert_eq!(entries.len(), MAX_ENTRIES);
+        assert_eq!(entries.last().unwrap().0, "k31");
+        assert_eq!(errors.len(), 8);
+        assert_eq!(
+            errors[0],
+            TraceStateError::TooManyEntries { key: "k32".into() }
+        );
+    }
+
+    #[test]
+    fn validate_key_lengths() {
+        let long_simple = format!("a{}", "b".repeat(255));
+        let too_long_simple = format!("a{}", "b".repeat(256));
+        let long_tenant = format!("{}@{}", "t".repeat(241), "s".repeat(14));
+        let too_long_system = format!("t@{}", "s".repeat(15));
+        assert!(is_valid_key(&long_simple));
+        assert!(!is_valid_key(&too_long_simple));
+        assert!(is_valid_key(&long_tenant));
+        assert!(!is_valid_key(&too_long_system));
+        assert!(is_valid_key("1tenant@sys"));
+        assert!(!is_valid_key("1simple"));
+    }
+
+    #[test]
+    fn round_trips_through_trace_state() {
+        let header = "rojo=00f067aa0ba902b7,tenant@vendor=a b,congo=t61rcWkgMzE";
+        let (entries, _) = parse(header);
+        let trace_state = opentelemetry::trace::TraceState::from_key_value(entries).unwrap();
+        assert_eq!(trace_state.header(), header);
+    }
+}
@@ -40,7 +40,7 @@ declare module 'wasi:otel/tracing@0.2.0-rc.2' {
   /**
    * Carries system-specific configuration data, represented as a list of key-value pairs. `trace-state` allows multiple tracing systems to participate in the same trace.
    * 
-   * If any invalid keys or values are provided then the `trace-state` will be treated as an empty list.
+   * Entries must follow the W3C `tracestate` key and value grammar, and at most 32 entries are kept. Invalid entries are discarded.
    */
   export type TraceState = Array<[string, string]>;
   /**